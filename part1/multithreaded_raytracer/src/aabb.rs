@@ -1,27 +1,81 @@
 use crate::{Point, Ray};
 
+#[derive(Clone, Copy, Debug)]
 pub struct AABB {
     pub min: Point,
     pub max: Point,
 }
 
 impl AABB {
+    pub fn new(min: Point, max: Point) -> AABB {
+        AABB {
+            min,
+            max,
+        }
+    }
+
     pub fn hit_box(&self, r: Ray, t_min: f32, t_max: f32) -> bool { // Bounding Volume Requirement
-        let mut t_min_aabb= t_min;
+        let mut t_min_aabb = t_min;
         let mut t_max_aabb = t_max;
         for a in 0..3 {
-            let t0a = (self.min[a] - r.origin[a]) / r.direction[a];
-            let t0b = (self.max[a] - r.origin[a]) / r.direction[a];
-            let t0 = t0a.min(t0b);
-
-            let t1a = (self.min[a] - r.origin[a]) / r.direction[a];
-            let t1b = (self.max[a] - r.origin[a]) / r.direction[a];
-            let t1 = t1a.max(t1b);
+            let inv_d = 1.0 / r.direction[a];
+            let mut t0 = (self.min[a] - r.origin[a]) * inv_d;
+            let mut t1 = (self.max[a] - r.origin[a]) * inv_d;
+            if inv_d < 0.0 {
+                std::mem::swap(&mut t0, &mut t1);
+            }
 
             t_min_aabb = t0.max(t_min_aabb);
             t_max_aabb = t1.min(t_max_aabb);
             if t_max_aabb <= t_min_aabb { return false; }
         }
-        return true;
+        true
+    }
+
+    pub fn surrounding_box(box0: AABB, box1: AABB) -> AABB {
+        let min = Point::new(box0.min.x.min(box1.min.x),
+                             box0.min.y.min(box1.min.y),
+                             box0.min.z.min(box1.min.z));
+        let max = Point::new(box0.max.x.max(box1.max.x),
+                             box0.max.y.max(box1.max.y),
+                             box0.max.z.max(box1.max.z));
+        AABB::new(min, max)
+    }
+
+    pub fn centroid(&self) -> Point {
+        0.5 * (self.min + self.max)
+    }
+
+    pub fn longest_axis(&self) -> u8 {
+        let extent = self.max - self.min;
+        if extent.x > extent.y && extent.x > extent.z {
+            0
+        } else if extent.y > extent.z {
+            1
+        } else {
+            2
+        }
     }
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+#[test]
+fn ray_through_box_hits() {
+    let b = AABB::new(Point::new(-1.0, -1.0, -1.0), Point::new(1.0, 1.0, 1.0));
+    let r = Ray::new(Point::new(0.0, 0.0, -5.0), Point::new(0.0, 0.0, 1.0));
+    assert!(b.hit_box(r, 0.001, f32::INFINITY));
+}
+
+#[test]
+fn ray_beside_box_misses() {
+    let b = AABB::new(Point::new(-1.0, -1.0, -1.0), Point::new(1.0, 1.0, 1.0));
+    let r = Ray::new(Point::new(2.0, 0.0, -5.0), Point::new(0.0, 0.0, 1.0));
+    assert!(!b.hit_box(r, 0.001, f32::INFINITY));
+}
+
+#[test]
+fn ray_pointing_away_misses() {
+    let b = AABB::new(Point::new(-1.0, -1.0, -1.0), Point::new(1.0, 1.0, 1.0));
+    let r = Ray::new(Point::new(0.0, 0.0, -5.0), Point::new(0.0, 0.0, -1.0));
+    assert!(!b.hit_box(r, 0.001, f32::INFINITY));
+}
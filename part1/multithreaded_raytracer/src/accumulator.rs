@@ -0,0 +1,130 @@
+use crate::framebuffer::Framebuffer;
+use crate::image::Image;
+use crate::point::Point;
+use crate::row_data::RowData;
+use crate::utility::{clamp, luminance};
+
+// Brightness below which relative error is measured as if against this, so
+// near-black pixels don't keep sampling to chase noise nobody can see.
+const DARK: f32 = 0.01;
+
+// Running radiance sums for every pixel, rows top to bottom, so a render can
+// be built up over any number of passes and looked at in between. `squares`
+// sums each sample's squared luminance, to tell how noisy a pixel still is.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Accumulator {
+    pub width: u32,
+    pub height: u32,
+    pub sums: Vec<Point>,
+    pub squares: Vec<f32>,
+    pub samples: Vec<u32>,
+}
+
+impl Accumulator {
+    pub fn new(width: u32, height: u32) -> Accumulator {
+        let pixels = width as usize * height as usize;
+        Accumulator {
+            width,
+            height,
+            sums: vec![Point::default(); pixels],
+            squares: vec![0.0; pixels],
+            samples: vec![0; pixels],
+        }
+    }
+
+    pub fn add_row(&mut self, row_data: &RowData) {
+        let i = row_data.index as usize;
+        for (offset, color) in row_data.colors.iter().enumerate() {
+            let samples = row_data.samples[offset];
+            self.sums[i + offset] = self.sums[i + offset] + samples as f32 * *color;
+            self.squares[i + offset] += row_data.squares[offset];
+            self.samples[i + offset] += samples;
+        }
+    }
+
+    // Samples every pixel has so far.
+    pub fn samples_done(&self) -> u32 {
+        self.samples.iter().copied().min().unwrap_or(0)
+    }
+
+    // Standard error of the pixel's mean luminance relative to the mean
+    // itself. Infinite until there are two samples to compare.
+    pub fn relative_error(&self, pixel: usize) -> f32 {
+        let n = self.samples[pixel] as f32;
+        if n < 2.0 {
+            return f32::INFINITY;
+        }
+        let mean = luminance(self.sums[pixel]) / n;
+        let variance = ((self.squares[pixel] - n * mean * mean) / (n - 1.0)).max(0.0);
+        (variance / n).sqrt() / mean.max(DARK)
+    }
+
+    // The average so far. Pixels without samples yet are black.
+    pub fn to_framebuffer(&self) -> Framebuffer {
+        let mut framebuffer = Framebuffer::new(self.width, self.height);
+        for ((pixel, &sum), &samples) in framebuffer.pixels.iter_mut().zip(&self.sums).zip(&self.samples) {
+            if samples > 0 {
+                *pixel = (1.0 / samples as f32) * sum;
+            }
+        }
+        framebuffer
+    }
+
+    // How many samples each pixel got, from black for none through red and
+    // yellow to white for the most any pixel got.
+    pub fn sample_heatmap(&self) -> Image {
+        let mut image = Image::new(self.width, self.height);
+        let most = self.samples.iter().copied().max().unwrap_or(0).max(1) as f32;
+        for (&samples, rgb) in self.samples.iter().zip(image.data.chunks_mut(3)) {
+            let t = 3.0 * samples as f32 / most;
+            for (channel, value) in rgb.iter_mut().enumerate() {
+                *value = (255.0 * clamp(t - channel as f32, 0.0, 1.0)).round() as u8;
+            }
+        }
+        image
+    }
+}
+
+#[cfg(test)]
+#[test]
+fn averages_over_every_pass() {
+    let mut accumulator = Accumulator::new(3, 2);
+    let mut row_data = RowData::new(3, 2);
+    row_data.push_color(Point::new(4.0, 0.0, 2.0), 1);
+    row_data.push_color(Point::new(1.0, 1.0, 1.0), 1);
+    accumulator.add_row(&row_data);
+    let mut row_data = RowData::new(3, 1);
+    row_data.push_color(Point::new(6.0, 3.0, 0.0), 3);
+    accumulator.add_row(&row_data);
+
+    assert_eq!(accumulator.samples, [0, 0, 0, 4, 1, 0]);
+    assert_eq!(accumulator.samples_done(), 0);
+    let framebuffer = accumulator.to_framebuffer();
+    assert_eq!(framebuffer.pixels[3], Point::new(2.5, 0.75, 0.5));
+    assert_eq!(framebuffer.pixels[4], Point::new(1.0, 1.0, 1.0));
+    assert_eq!(framebuffer.pixels[0], Point::default());
+}
+
+#[test]
+fn noisy_pixels_have_larger_errors() {
+    let mut accumulator = Accumulator::new(3, 1);
+    let mut row_data = RowData::new(0, 3);
+    let grey = Point::new(0.5, 0.5, 0.5);
+    // Four samples of 0.5 each, then two of 0 and two of 1.
+    row_data.push_samples(4.0 * grey, 4.0 * 0.25, 4);
+    row_data.push_samples(Point::new(2.0, 2.0, 2.0), 2.0, 4);
+    row_data.push_samples(grey, 0.25, 1);
+    accumulator.add_row(&row_data);
+
+    assert!(accumulator.relative_error(0) < 1e-3);
+    // Variance 1/3 over 4 samples, against a mean of 0.5.
+    assert!((accumulator.relative_error(1) - (1.0f32 / 12.0).sqrt() / 0.5).abs() < 1e-4);
+    assert_eq!(accumulator.relative_error(2), f32::INFINITY);
+}
+
+#[test]
+fn heatmap_runs_from_black_to_white() {
+    let mut accumulator = Accumulator::new(3, 1);
+    accumulator.samples = vec![0, 2, 6];
+    assert_eq!(accumulator.sample_heatmap().data, [0, 0, 0, 255, 0, 0, 255, 255, 255]);
+}
@@ -1,29 +1,170 @@
 use crate::aabb::AABB;
-use crate::{Ray};
+use crate::hittable::HitRecord;
+use crate::{Point, Ray};
 
+const MAX_LEAF_SIZE: usize = 2;
+const STACK_SIZE: usize = 64;
+
+enum Node {
+    Leaf {
+        bounding_box: AABB,
+        start: usize,
+        count: usize,
+    },
+    Branch {
+        bounding_box: AABB,
+        left: usize,
+        right: usize,
+        axis: u8,
+    },
+}
+
+impl Node {
+    fn bounding_box(&self) -> AABB {
+        match self {
+            Node::Leaf { bounding_box, .. } => *bounding_box,
+            Node::Branch { bounding_box, .. } => *bounding_box,
+        }
+    }
+}
+
+struct Primitive {
+    index: usize,
+    bounding_box: AABB,
+    centroid: Point,
+}
+
+// Bounding Volume Requirement
+// Median split tree over the bounding boxes of a set of objects. The tree only
+// stores indices, the objects themselves stay with the owner.
 pub struct BVH {
-    boxes: Vec<AABB>
+    nodes: Vec<Node>,
+    indices: Vec<usize>,
 }
 
 impl BVH {
-    pub fn check_ray(&self, r: Ray, t_min:f32, t_max:f32) -> bool { // Bounding Volume Requirement
-        for bounding_box in &self.boxes {
-            if bounding_box.hit_box(r, t_min, t_max) {
-                return true;
-            }
+    pub fn new(boxes: &[(usize, AABB)]) -> BVH {
+        let mut primitives: Vec<Primitive> = boxes.iter()
+            .map(|&(index, bounding_box)| Primitive {
+                index,
+                bounding_box,
+                centroid: bounding_box.centroid(),
+            })
+            .collect();
+        let mut bvh = BVH {
+            nodes: Vec::with_capacity(2 * primitives.len()),
+            indices: Vec::with_capacity(primitives.len()),
+        };
+        if !primitives.is_empty() {
+            bvh.build(&mut primitives);
         }
-        return false;
+        bvh
     }
 
-    pub fn new() -> BVH {
-        BVH {
-            boxes: Vec::new(),
+    fn build(&mut self, primitives: &mut [Primitive]) -> usize {
+        let bounding_box = primitives[1..].iter()
+            .fold(primitives[0].bounding_box, |b, p| AABB::surrounding_box(b, p.bounding_box));
+
+        if primitives.len() <= MAX_LEAF_SIZE {
+            let start = self.indices.len();
+            self.indices.extend(primitives.iter().map(|p| p.index));
+            self.nodes.push(Node::Leaf { bounding_box, start, count: primitives.len() });
+            return self.nodes.len() - 1;
         }
+
+        let centroid_box = primitives[1..].iter()
+            .fold(AABB::new(primitives[0].centroid, primitives[0].centroid),
+                  |b, p| AABB::surrounding_box(b, AABB::new(p.centroid, p.centroid)));
+        let axis = centroid_box.longest_axis();
+        let mid = primitives.len() / 2;
+        primitives.select_nth_unstable_by(mid, |a, b| a.centroid[axis].total_cmp(&b.centroid[axis]));
+
+        let node_i = self.nodes.len();
+        self.nodes.push(Node::Leaf { bounding_box, start: 0, count: 0 }); // replaced below
+        let (left_primitives, right_primitives) = primitives.split_at_mut(mid);
+        let left = self.build(left_primitives);
+        let right = self.build(right_primitives);
+        self.nodes[node_i] = Node::Branch { bounding_box, left, right, axis };
+        node_i
     }
 
-    pub fn add(&mut self, b: AABB) {
-        self.boxes.push(b);
+    /// Walks the tree front to back and returns the index of the closest object
+    /// along with its hit record. `hit_object` is called with an object index and
+    /// the current closest distance, which shrinks as hits are found.
+    pub fn hit<'a, F>(&self, r: Ray, t_min: f32, t_max: f32, mut hit_object: F) -> Option<(usize, HitRecord<'a>)>
+        where F: FnMut(usize, f32) -> Option<HitRecord<'a>>
+    {
+        if self.nodes.is_empty() {
+            return None;
+        }
+        let mut closest: Option<(usize, HitRecord<'a>)> = None;
+        let mut closest_so_far = t_max;
+        let mut stack = [0usize; STACK_SIZE];
+        let mut stack_len = 1;
+
+        while stack_len > 0 {
+            stack_len -= 1;
+            let node = &self.nodes[stack[stack_len]];
+            if !node.bounding_box().hit_box(r, t_min, closest_so_far) {
+                continue;
+            }
+            match *node {
+                Node::Leaf { start, count, .. } => {
+                    for &index in &self.indices[start..start + count] {
+                        if let Some(rec) = hit_object(index, closest_so_far) {
+                            closest_so_far = rec.t;
+                            closest = Some((index, rec));
+                        }
+                    }
+                }
+                Node::Branch { left, right, axis, .. } => {
+                    // Push the far child first so the near one is visited first.
+                    let (near, far) = if r.direction[axis] < 0.0 { (right, left) } else { (left, right) };
+                    stack[stack_len] = far;
+                    stack[stack_len + 1] = near;
+                    stack_len += 2;
+                }
+            }
+        }
+        closest
     }
+}
+
+#[cfg(test)]
+#[test]
+fn matches_brute_force_closest_hit() {
+    use rand::Rng;
+    use std::sync::Arc;
+    use crate::hittable::Hittable;
+    use crate::material::{Lambertian, Material};
+    use crate::sphere::Sphere;
 
+    let mut rng = crate::random::Pcg32::new(7, 0);
+    let grey: Arc<dyn Material> = Arc::new(Lambertian::new(Point::new(0.5, 0.5, 0.5)));
+    let spheres: Vec<Sphere> = (0..500)
+        .map(|_| Sphere::new(
+            crate::utility::random_point_range(&mut rng, -20.0, 20.0),
+            rng.gen_range(0.1..2.0),
+            grey.clone()))
+        .collect();
+    let boxes: Vec<_> = spheres.iter()
+        .enumerate()
+        .map(|(i, s)| (i, s.get_bounding_box().unwrap()))
+        .collect();
+    let bvh = BVH::new(&boxes);
 
-}
\ No newline at end of file
+    for _ in 0..1000 {
+        let r = Ray::new(crate::utility::random_point_range(&mut rng, -30.0, 30.0),
+                         crate::utility::random_point_range(&mut rng, -1.0, 1.0));
+        let mut expected = None;
+        let mut closest_so_far = f32::INFINITY;
+        for (i, s) in spheres.iter().enumerate() {
+            if let Some(rec) = s.hit(r, 0.001, closest_so_far, &mut rng) {
+                closest_so_far = rec.t;
+                expected = Some(i);
+            }
+        }
+        let found = bvh.hit(r, 0.001, f32::INFINITY, |i, t_max| spheres[i].hit(r, 0.001, t_max, &mut rng));
+        assert_eq!(found.map(|(i, _)| i), expected);
+    }
+}
@@ -1,8 +1,7 @@
-use rand::rngs::ThreadRng;
-
 use crate::Point;
 use crate::ray::Ray;
-use crate::utility::{cross, random_in_unit_disk, unit_vector};
+use crate::sampler::Sampler;
+use crate::utility::{cross, sample_in_unit_disk, unit_vector};
 
 pub struct Camera {
     pub lookfrom: Point,
@@ -13,6 +12,9 @@ pub struct Camera {
     pub aspect_ratio: f32,
     pub aperture: f32,
     pub focus_dist: f32,
+    // Rays get a time spread evenly over this interval.
+    pub shutter_open: f32,
+    pub shutter_close: f32,
 
     origin: Point,
     lower_left_corner: Point,
@@ -25,19 +27,36 @@ pub struct Camera {
 }
 
 pub trait Cast: Sync + Send {
-    fn get_ray(&self, s: f32, t: f32, rng: &mut ThreadRng) -> Ray;
+    fn get_ray(&self, s: f32, t: f32, sampler: &mut dyn Sampler) -> Ray;
 }
 
 impl Cast for Camera {
-    fn get_ray(&self, s: f32, t: f32, rng: &mut ThreadRng) -> Ray {
-        let rd = self.lens_radius * random_in_unit_disk(rng);
+    // Takes the lens position and then the time from `sampler`, both drawn
+    // even when the lens is a pinhole or the shutter is closed, so later
+    // dimensions don't shift around.
+    fn get_ray(&self, s: f32, t: f32, sampler: &mut dyn Sampler) -> Ray {
+        let rd = self.lens_radius * sample_in_unit_disk(sampler.next_2d());
         let offset = self.u * rd.x + self.v * rd.y;
-        Ray::new(self.origin + offset,
-                 self.lower_left_corner + s * self.horizontal + t * self.vertical - self.origin - offset)
+        let time = self.shutter_open + sampler.next_1d() * (self.shutter_close - self.shutter_open);
+        Ray::with_time(self.origin + offset,
+                       self.lower_left_corner + s * self.horizontal + t * self.vertical - self.origin - offset,
+                       time)
     }
 }
 
 impl Camera {
+    pub fn with_aspect_ratio(&self, aspect_ratio: f32) -> Camera {
+        Camera::new(self.lookfrom, self.lookat, self.vup, self.vfov, aspect_ratio, self.aperture, self.focus_dist)
+            .with_shutter(self.shutter_open, self.shutter_close)
+    }
+
+    // The shutter starts and ends at time 0 unless set here.
+    pub fn with_shutter(mut self, open: f32, close: f32) -> Camera {
+        self.shutter_open = open;
+        self.shutter_close = close;
+        self
+    }
+
     pub fn new(lookfrom: Point,
                lookat: Point,
                vup: Point,
@@ -73,6 +92,8 @@ impl Camera {
             aspect_ratio,
             aperture,
             focus_dist,
+            shutter_open: 0.0,
+            shutter_close: 0.0,
             origin,
             lower_left_corner,
             horizontal,
@@ -0,0 +1,187 @@
+use std::fs;
+use std::io;
+use std::path::Path;
+
+use crate::accumulator::Accumulator;
+use crate::point::Point;
+use crate::render_settings::RenderSettings;
+
+const MAGIC: &[u8; 8] = b"RTCKPT02";
+const HEADER_LEN: usize = 36;
+const PIXEL_LEN: usize = 20;
+
+fn invalid(message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, format!("bad checkpoint: {}", message))
+}
+
+// Everything needed to carry on a render later, possibly on another machine:
+// the radiance sums and sample counts so far, plus the seed and scene hash to
+// make sure the new samples belong to the same picture. The stratified
+// sampler's strata are kept too, since they follow the sample count and that
+// may go up on resume.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Checkpoint {
+    pub seed: u64,
+    pub scene_hash: u64,
+    pub strata: u32,
+    pub accumulator: Accumulator,
+}
+
+impl Checkpoint {
+    pub fn new(seed: u64, scene_hash: u64, strata: u32, accumulator: Accumulator) -> Checkpoint {
+        Checkpoint {
+            seed,
+            scene_hash,
+            strata,
+            accumulator,
+        }
+    }
+
+    // Little-endian throughout: the magic, width, height, seed, scene hash and
+    // strata, then for every pixel its red, green and blue sums, its sum of squared
+    // luminances and its sample count.
+    pub fn encode(&self) -> Vec<u8> {
+        let accumulator = &self.accumulator;
+        let mut out = Vec::with_capacity(HEADER_LEN + PIXEL_LEN * accumulator.sums.len());
+        out.extend_from_slice(MAGIC);
+        out.extend_from_slice(&accumulator.width.to_le_bytes());
+        out.extend_from_slice(&accumulator.height.to_le_bytes());
+        out.extend_from_slice(&self.seed.to_le_bytes());
+        out.extend_from_slice(&self.scene_hash.to_le_bytes());
+        out.extend_from_slice(&self.strata.to_le_bytes());
+        for ((sum, squares), samples) in accumulator.sums.iter().zip(&accumulator.squares).zip(&accumulator.samples) {
+            for value in [sum.x, sum.y, sum.z, *squares] {
+                out.extend_from_slice(&value.to_le_bytes());
+            }
+            out.extend_from_slice(&samples.to_le_bytes());
+        }
+        out
+    }
+
+    // Writes next to `path` first and renames over it, so a render killed
+    // halfway through saving still leaves the previous checkpoint intact.
+    pub fn write_file(&self, path: &Path) -> io::Result<()> {
+        let mut partial = path.as_os_str().to_owned();
+        partial.push(".partial");
+        fs::write(&partial, self.encode())?;
+        fs::rename(&partial, path)
+    }
+
+    pub fn decode(bytes: &[u8]) -> io::Result<Checkpoint> {
+        if bytes.len() < HEADER_LEN || &bytes[..8] != MAGIC {
+            return Err(invalid("not a checkpoint file"));
+        }
+        let u32_at = |i: usize| u32::from_le_bytes(bytes[i..i + 4].try_into().unwrap());
+        let u64_at = |i: usize| u64::from_le_bytes(bytes[i..i + 8].try_into().unwrap());
+        let f32_at = |i: usize| f32::from_le_bytes(bytes[i..i + 4].try_into().unwrap());
+
+        let (width, height) = (u32_at(8), u32_at(12));
+        let too_big = || invalid(&format!("a {}x{} render is too large", width, height));
+        let pixels = (width as usize).checked_mul(height as usize).ok_or_else(too_big)?;
+        let length = PIXEL_LEN.checked_mul(pixels).and_then(|n| n.checked_add(HEADER_LEN)).ok_or_else(too_big)?;
+        if bytes.len() != length {
+            return Err(invalid(&format!("expected {} pixels for a {}x{} render", pixels, width, height)));
+        }
+        let mut accumulator = Accumulator::new(width, height);
+        for i in 0..pixels {
+            let at = HEADER_LEN + PIXEL_LEN * i;
+            accumulator.sums[i] = Point::new(f32_at(at), f32_at(at + 4), f32_at(at + 8));
+            accumulator.squares[i] = f32_at(at + 12);
+            accumulator.samples[i] = u32_at(at + 16);
+        }
+        Ok(Checkpoint::new(u64_at(16), u64_at(24), u32_at(32), accumulator))
+    }
+
+    pub fn read_file(path: &Path) -> io::Result<Checkpoint> {
+        Checkpoint::decode(&fs::read(path)?)
+    }
+}
+
+// FNV-1a over the scene source and the settings that change what a sample
+// looks like. The sample count is left out so a render can be resumed with
+// more samples. Files the scene refers to aren't included.
+pub fn scene_hash(source: &str, settings: &RenderSettings) -> u64 {
+    let mut hash = 0xcbf29ce484222325u64;
+    let mut feed = |bytes: &[u8]| {
+        for &b in bytes {
+            hash = (hash ^ b as u64).wrapping_mul(0x100000001b3);
+        }
+    };
+    feed(source.as_bytes());
+    feed(&settings.width.to_le_bytes());
+    feed(&settings.height.to_le_bytes());
+    feed(&settings.depth.to_le_bytes());
+    feed(format!("{:?}", settings.sampler).as_bytes());
+    hash
+}
+
+#[cfg(test)]
+#[test]
+fn round_trips() {
+    let mut accumulator = Accumulator::new(3, 2);
+    accumulator.sums[1] = Point::new(1.5, -0.0, 1e9);
+    accumulator.squares[1] = 2.25;
+    accumulator.samples[1] = 7;
+    accumulator.samples[5] = 2;
+    let checkpoint = Checkpoint::new(42, 0xdead_beef_0bad_f00d, 16, accumulator);
+    let bytes = checkpoint.encode();
+    assert_eq!(bytes.len(), HEADER_LEN + PIXEL_LEN * 6);
+    assert_eq!(Checkpoint::decode(&bytes).unwrap(), checkpoint);
+}
+
+#[test]
+fn rejects_other_files() {
+    let bytes = Checkpoint::new(1, 2, 3, Accumulator::new(4, 4)).encode();
+    assert!(Checkpoint::decode(&bytes[..bytes.len() - 1]).is_err());
+    assert!(Checkpoint::decode(b"P6 1 1 255\n\0\0\0").is_err());
+    assert!(Checkpoint::decode(&bytes[..HEADER_LEN - 1]).is_err());
+}
+
+#[test]
+fn rejects_impossible_sizes() {
+    let mut bytes = Checkpoint::new(1, 2, 3, Accumulator::new(1, 1)).encode();
+    bytes[8..16].copy_from_slice(&[0xff; 8]);
+    assert!(Checkpoint::decode(&bytes).is_err());
+}
+
+#[test]
+fn hash_follows_scene_and_settings() {
+    let settings = RenderSettings::default();
+    let source = "sphere center=0,0,-1 radius=0.5";
+    assert_eq!(scene_hash(source, &settings), scene_hash(source, &settings));
+    assert_ne!(scene_hash(source, &settings), scene_hash("sphere center=0,0,-1 radius=0.6", &settings));
+    let more_samples = RenderSettings { samples_per_pixel: settings.samples_per_pixel * 2, ..settings };
+    assert_eq!(scene_hash(source, &settings), scene_hash(source, &more_samples));
+    let wider = RenderSettings { width: settings.width + 1, ..settings };
+    assert_ne!(scene_hash(source, &settings), scene_hash(source, &wider));
+}
+
+#[test]
+fn resumed_stratified_render_keeps_its_strata() {
+    use crate::render::{render_hdr, render_progressive};
+    use crate::sampler::SamplerKind;
+    use crate::scene::Scene;
+
+    let scene = Scene::parse("camera aperture=0.3\n\
+                              material tin metal albedo=0.8,0.6,0.4 fuzz=0.4\n\
+                              sphere center=0,0,-1 radius=0.5 material=tin\n").unwrap();
+    let first = RenderSettings {
+        width: 6,
+        height: 4,
+        samples_per_pixel: 16,
+        depth: 4,
+        threads: 2,
+        sampler: SamplerKind::Stratified,
+        ..RenderSettings::default()
+    };
+    let accumulator = render_progressive(&scene, &first, Accumulator::new(6, 4), 16, |_, _| {}, |_, _| {});
+    let saved = Checkpoint::decode(&Checkpoint::new(0, 0, first.strata_count(), accumulator).encode()).unwrap();
+    assert_eq!(saved.strata, 16);
+
+    let more = RenderSettings { samples_per_pixel: 64, strata: Some(saved.strata), ..first };
+    let resumed = render_progressive(&scene, &more, saved.accumulator, 16, |_, _| {}, |_, _| {});
+    let whole = render_hdr(&scene, &more);
+    for (a, b) in resumed.to_framebuffer().pixels.iter().zip(&whole.pixels) {
+        assert!((*a - *b).length() < 1e-4 * (1.0 + b.length()), "{:?} {:?}", a, b);
+    }
+}
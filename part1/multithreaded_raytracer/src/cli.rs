@@ -0,0 +1,301 @@
+use std::path::PathBuf;
+use std::str::FromStr;
+
+use final_project::output::OutputFormat;
+use final_project::sampler::SamplerKind;
+use final_project::tonemap::Operator;
+
+pub const USAGE: &str = "\
+Usage: final_project [OPTIONS] [SCENE]
+
+Renders SCENE (a .scene file) or the built-in scene if none is given.
+Options left out fall back to the scene's render settings.
+
+Options:
+      --scene <PATH>     Scene file to render
+      --width <PIXELS>   Image width
+      --height <PIXELS>  Image height. With only one of width and height
+                         the other follows the scene's aspect ratio
+      --samples <N>      Samples per pixel
+      --depth <N>        Maximum bounces per ray
+      --threads <N>      Worker threads [default: available cores]
+  -o, --output <PATH>    Output file [default: output.ppm]
+      --format <FORMAT>  Output format, guessed from the output file's
+                         extension if left out [possible values: ppm, png, hdr]
+      --tonemap <NAME>   Tonemapping for 8-bit output, HDR output stays
+                         linear [possible values: clamp, reinhard, aces]
+      --exposure <STOPS> Exposure adjustment before tonemapping
+      --seed <N>         Seed for the random number generator
+      --sampler <NAME>   Sample pattern for pixels, lens and bounces
+                         [possible values: independent, stratified, halton, sobol]
+      --pass-samples <N> Samples per pixel in each rendering pass [default: 1]
+      --preview-passes <N>
+                         Write the image so far to the output file every N
+                         passes
+      --preview-seconds <SECONDS>
+                         Write the image so far to the output file at most
+                         every SECONDS seconds
+      --adaptive <ERROR> Sample noisy pixels more and converged ones less,
+                         stopping a pixel once its relative error is below
+                         ERROR (e.g. 0.02)
+      --heatmap <PATH>   Also write how many samples each pixel got, as a
+                         .ppm or .png image
+      --checkpoint <PATH>
+                         Save the render state here with every preview and
+                         at the end, and resume from it if it exists. With no
+                         preview option, saves every 60 seconds
+  -h, --help             Print this help
+";
+
+#[derive(Debug, PartialEq)]
+pub struct Options {
+    pub scene: Option<PathBuf>,
+    pub width: Option<u32>,
+    pub height: Option<u32>,
+    pub samples_per_pixel: Option<i32>,
+    pub depth: Option<i32>,
+    pub threads: Option<usize>,
+    pub output: PathBuf,
+    pub format: OutputFormat,
+    pub tonemap: Option<Operator>,
+    pub exposure: Option<f32>,
+    pub seed: Option<u64>,
+    pub sampler: Option<SamplerKind>,
+    pub pass_samples: u32,
+    pub preview_passes: Option<u32>,
+    pub preview_seconds: Option<f32>,
+    pub checkpoint: Option<PathBuf>,
+    pub adaptive: Option<f32>,
+    pub heatmap: Option<PathBuf>,
+}
+
+#[derive(Debug, PartialEq)]
+pub enum Command {
+    Render(Options),
+    Help,
+}
+
+fn parse_positive<T: FromStr + PartialOrd + Default>(flag: &str, value: &str) -> Result<T, String> {
+    match value.parse::<T>() {
+        Ok(n) if n > T::default() => Ok(n),
+        _ => Err(format!("{} expects a whole number greater than zero, found '{}'", flag, value)),
+    }
+}
+
+pub fn parse_args<I: IntoIterator<Item = String>>(args: I) -> Result<Command, String> {
+    let mut scene = None;
+    let mut width = None;
+    let mut height = None;
+    let mut samples_per_pixel = None;
+    let mut depth = None;
+    let mut threads = None;
+    let mut output = None;
+    let mut format = None;
+    let mut tonemap = None;
+    let mut exposure = None;
+    let mut seed = None;
+    let mut sampler = None;
+    let mut pass_samples = None;
+    let mut preview_passes = None;
+    let mut preview_seconds = None;
+    let mut checkpoint = None;
+    let mut adaptive = None;
+    let mut heatmap = None;
+
+    let mut args = args.into_iter();
+    while let Some(arg) = args.next() {
+        if arg == "-h" || arg == "--help" {
+            return Ok(Command::Help);
+        }
+        if !arg.starts_with('-') || arg == "-" {
+            if scene.is_some() {
+                return Err(format!("unexpected argument '{}', only one scene can be rendered", arg));
+            }
+            scene = Some(PathBuf::from(arg));
+            continue;
+        }
+
+        let (flag, inline_value) = match arg.split_once('=') {
+            Some((flag, value)) => (flag.to_owned(), Some(value.to_owned())),
+            None => (arg.clone(), None),
+        };
+        let known = ["--scene", "--width", "--height", "--samples", "--depth", "--threads",
+                     "-o", "--output", "--format", "--tonemap", "--exposure", "--seed", "--sampler",
+                     "--pass-samples", "--preview-passes", "--preview-seconds", "--checkpoint",
+                     "--adaptive", "--heatmap"];
+        if !known.contains(&flag.as_str()) {
+            return Err(format!("unknown option '{}'", flag));
+        }
+        let value = match inline_value.or_else(|| args.next()) {
+            Some(value) => value,
+            None => return Err(format!("{} expects a value", flag)),
+        };
+
+        match flag.as_str() {
+            "--scene" => scene = Some(PathBuf::from(value)),
+            "--width" => width = Some(parse_positive(&flag, &value)?),
+            "--height" => height = Some(parse_positive(&flag, &value)?),
+            "--samples" => samples_per_pixel = Some(parse_positive(&flag, &value)?),
+            "--depth" => depth = Some(parse_positive(&flag, &value)?),
+            "--threads" => threads = Some(parse_positive(&flag, &value)?),
+            "-o" | "--output" => output = Some(PathBuf::from(value)),
+            "--format" => {
+                format = Some(OutputFormat::from_name(&value)
+                    .ok_or_else(|| format!("unknown output format '{}'", value))?);
+            }
+            "--tonemap" => {
+                tonemap = Some(Operator::from_name(&value)
+                    .ok_or_else(|| format!("unknown tonemap '{}'", value))?);
+            }
+            "--exposure" => {
+                exposure = Some(value.parse::<f32>().ok().filter(|e| e.is_finite())
+                    .ok_or_else(|| format!("--exposure expects a number, found '{}'", value))?);
+            }
+            "--seed" => {
+                seed = Some(value.parse::<u64>()
+                    .map_err(|_| format!("--seed expects a whole number, found '{}'", value))?);
+            }
+            "--sampler" => {
+                sampler = Some(SamplerKind::from_name(&value)
+                    .ok_or_else(|| format!("unknown sampler '{}'", value))?);
+            }
+            "--pass-samples" => pass_samples = Some(parse_positive(&flag, &value)?),
+            "--preview-passes" => preview_passes = Some(parse_positive(&flag, &value)?),
+            "--preview-seconds" => {
+                preview_seconds = Some(value.parse::<f32>().ok().filter(|s| s.is_finite() && *s > 0.0)
+                    .ok_or_else(|| format!("--preview-seconds expects a number greater than zero, found '{}'", value))?);
+            }
+            "--checkpoint" => checkpoint = Some(PathBuf::from(value)),
+            "--adaptive" => {
+                adaptive = Some(value.parse::<f32>().ok().filter(|e| e.is_finite() && *e > 0.0)
+                    .ok_or_else(|| format!("--adaptive expects a number greater than zero, found '{}'", value))?);
+            }
+            "--heatmap" => {
+                let path = PathBuf::from(value);
+                if !matches!(OutputFormat::from_path(&path), Some(OutputFormat::Ppm | OutputFormat::Png)) {
+                    return Err(format!("can't write a heatmap to '{}', use .ppm or .png", path.display()));
+                }
+                heatmap = Some(path);
+            }
+            _ => unreachable!(),
+        }
+    }
+
+    let output = output.unwrap_or_else(|| PathBuf::from("output.ppm"));
+    let format = match format {
+        Some(format) => format,
+        None => OutputFormat::from_path(&output).ok_or_else(|| {
+            format!("can't tell the output format from '{}', pass --format", output.display())
+        })?,
+    };
+
+    Ok(Command::Render(Options {
+        scene,
+        width,
+        height,
+        samples_per_pixel,
+        depth,
+        threads,
+        output,
+        format,
+        tonemap,
+        exposure,
+        seed,
+        sampler,
+        pass_samples: pass_samples.unwrap_or(1),
+        preview_passes,
+        preview_seconds,
+        checkpoint,
+        adaptive,
+        heatmap,
+    }))
+}
+
+#[cfg(test)]
+fn parse(args: &[&str]) -> Result<Command, String> {
+    parse_args(args.iter().map(|a| a.to_string()))
+}
+
+#[test]
+fn defaults_without_arguments() {
+    let Command::Render(options) = parse(&[]).unwrap() else { panic!("expected render") };
+    assert_eq!(options.scene, None);
+    assert_eq!(options.output, PathBuf::from("output.ppm"));
+    assert_eq!(options.format, OutputFormat::Ppm);
+    assert_eq!((options.pass_samples, options.preview_passes, options.preview_seconds), (1, None, None));
+    let Command::Render(options) = parse(&["-o", "render.hdr"]).unwrap() else { panic!("expected render") };
+    assert_eq!(options.format, OutputFormat::Hdr);
+}
+
+#[test]
+fn reads_flags_in_both_forms() {
+    let Command::Render(options) = parse(&["room.scene", "--width", "640", "--height=360",
+                                           "--samples", "16", "--depth=8", "--threads", "4",
+                                           "-o", "out.ppm", "--seed", "7", "--sampler=sobol"]).unwrap() else { panic!("expected render") };
+    assert_eq!(options.scene, Some(PathBuf::from("room.scene")));
+    assert_eq!(options.width, Some(640));
+    assert_eq!(options.height, Some(360));
+    assert_eq!(options.samples_per_pixel, Some(16));
+    assert_eq!(options.depth, Some(8));
+    assert_eq!(options.threads, Some(4));
+    assert_eq!(options.seed, Some(7));
+    assert_eq!(options.sampler, Some(SamplerKind::Sobol));
+}
+
+#[test]
+fn reads_tonemapping() {
+    let Command::Render(options) = parse(&["--tonemap", "reinhard", "--exposure=-0.5"]).unwrap() else { panic!("expected render") };
+    assert_eq!(options.tonemap, Some(Operator::Reinhard));
+    assert_eq!(options.exposure, Some(-0.5));
+    assert!(parse(&["--tonemap", "filmic"]).is_err());
+    assert!(parse(&["--exposure", "bright"]).is_err());
+}
+
+#[test]
+fn reads_preview_options() {
+    let Command::Render(options) = parse(&["--pass-samples", "4", "--preview-passes=10", "--preview-seconds", "2.5"]).unwrap() else { panic!("expected render") };
+    assert_eq!(options.pass_samples, 4);
+    assert_eq!(options.preview_passes, Some(10));
+    assert_eq!(options.preview_seconds, Some(2.5));
+    assert_eq!(options.checkpoint, None);
+    let Command::Render(options) = parse(&["--checkpoint", "night.ckpt"]).unwrap() else { panic!("expected render") };
+    assert_eq!(options.checkpoint, Some(PathBuf::from("night.ckpt")));
+}
+
+#[test]
+fn reads_adaptive_options() {
+    let Command::Render(options) = parse(&["--adaptive", "0.02", "--heatmap=samples.png"]).unwrap() else { panic!("expected render") };
+    assert_eq!(options.adaptive, Some(0.02));
+    assert_eq!(options.heatmap, Some(PathBuf::from("samples.png")));
+}
+
+#[test]
+fn help_wins() {
+    assert_eq!(parse(&["--width", "10", "--help"]), Ok(Command::Help));
+}
+
+#[test]
+fn rejects_bad_values() {
+    assert!(parse(&["--width", "0"]).is_err());
+    assert!(parse(&["--samples", "-3"]).is_err());
+    assert!(parse(&["--threads"]).is_err());
+    assert!(parse(&["--frobnicate"]).is_err());
+    assert!(parse(&["a.scene", "b.scene"]).is_err());
+    assert!(parse(&["-o", "out.bmp"]).is_err());
+    assert!(parse(&["--format", "gif"]).is_err());
+    assert!(parse(&["--sampler", "random"]).is_err());
+    assert!(parse(&["--preview-seconds", "0"]).is_err());
+    assert!(parse(&["--preview-passes", "1.5"]).is_err());
+    assert!(parse(&["--adaptive", "-0.1"]).is_err());
+    assert!(parse(&["--heatmap", "samples.hdr"]).is_err());
+}
+
+#[test]
+fn format_follows_extension() {
+    let Command::Render(options) = parse(&["-o", "render.PNG"]).unwrap() else { panic!("expected render") };
+    assert_eq!(options.format, OutputFormat::Png);
+    let Command::Render(options) = parse(&["-o", "render.png", "--format", "ppm"]).unwrap() else { panic!("expected render") };
+    assert_eq!(options.format, OutputFormat::Ppm);
+    let Command::Render(options) = parse(&["-o", "render.hdr"]).unwrap() else { panic!("expected render") };
+    assert_eq!(options.format, OutputFormat::Hdr);
+}
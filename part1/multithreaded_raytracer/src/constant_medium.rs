@@ -0,0 +1,104 @@
+use std::sync::Arc;
+
+use rand::{Rng, RngCore};
+
+use crate::{Point, Ray};
+use crate::aabb::AABB;
+use crate::hittable::{HitRecord, Hittable};
+use crate::material::Material;
+
+// Fog, smoke or any other uniformly thin stuff filling a closed `boundary`.
+// A ray passing through is scattered somewhere inside with probability rising
+// with `density` and the distance it travels, and the hit then uses
+// `phase_function` (usually Isotropic) to pick the new direction.
+pub struct ConstantMedium {
+    pub boundary: Arc<dyn Hittable>,
+    pub density: f32,
+    pub phase_function: Arc<dyn Material>,
+}
+
+impl ConstantMedium {
+    pub fn new(boundary: Arc<dyn Hittable>, density: f32, phase_function: Arc<dyn Material>) -> ConstantMedium {
+        ConstantMedium {
+            boundary,
+            density,
+            phase_function,
+        }
+    }
+}
+
+impl Hittable for ConstantMedium {
+    fn hit(&self, r: Ray, t_min: f32, t_max: f32, rng: &mut dyn RngCore) -> Option<HitRecord<'_>> {
+        // Where the ray enters and leaves the boundary, even behind its origin,
+        // so rays starting inside the volume work too.
+        let entry = self.boundary.hit(r, f32::NEG_INFINITY, f32::INFINITY, rng)?;
+        let exit = self.boundary.hit(r, entry.t + 0.0001, f32::INFINITY, rng)?;
+        let t_enter = entry.t.max(t_min).max(0.0);
+        let t_exit = exit.t.min(t_max);
+        if t_enter >= t_exit {
+            return None;
+        }
+
+        let ray_length = r.direction.length();
+        let distance_inside = (t_exit - t_enter) * ray_length;
+        let hit_distance = -(1.0 - rng.gen::<f32>()).ln() / self.density;
+        if hit_distance > distance_inside {
+            return None;
+        }
+
+        let t = t_enter + hit_distance / ray_length;
+        // Inside a volume there is no surface, so the normal is arbitrary.
+        Some(HitRecord {
+            p: r.at(t),
+            normal: Point::new(1.0, 0.0, 0.0),
+            t,
+            front_face: true,
+            u: 0.0,
+            v: 0.0,
+            barycentric: (0.0, 0.0),
+            material: self.phase_function.as_ref(),
+        })
+    }
+
+    fn get_bounding_box(&self) -> Option<AABB> {
+        self.boundary.get_bounding_box()
+    }
+}
+
+#[cfg(test)]
+fn fog(density: f32) -> ConstantMedium {
+    use crate::material::Isotropic;
+    use crate::sphere::Sphere;
+
+    let isotropic: Arc<dyn Material> = Arc::new(Isotropic::new(Point::new(0.5, 0.5, 0.5)));
+    ConstantMedium::new(Arc::new(Sphere::new(Point::default(), 1.0, isotropic.clone())), density, isotropic)
+}
+
+#[test]
+fn scatters_inside_the_boundary() {
+    let mut rng = crate::random::Pcg32::new(7, 0);
+    let medium = fog(1.0);
+    let r = Ray::new(Point::new(0.0, 0.0, 5.0), Point::new(0.0, 0.0, -2.0));
+    for _ in 0..200 {
+        if let Some(rec) = medium.hit(r, 0.001, f32::INFINITY, &mut rng) {
+            assert!(rec.p.length() <= 1.0 + 1e-4);
+            assert!((r.at(rec.t) - rec.p).length() < 1e-5);
+        }
+    }
+    // Starting inside works, and nothing is found past t_max.
+    assert!((0..200).any(|_| medium.hit(Ray::new(Point::default(), Point::new(1.0, 0.0, 0.0)), 0.001, f32::INFINITY, &mut rng).is_some()));
+    assert!((0..200).all(|_| medium.hit(r, 0.001, 1.9, &mut rng).is_none()));
+    assert!(medium.hit(Ray::new(Point::new(0.0, 3.0, 5.0), Point::new(0.0, 0.0, -1.0)), 0.001, f32::INFINITY, &mut rng).is_none());
+}
+
+#[test]
+fn transmission_follows_beer_lambert() {
+    let mut rng = crate::random::Pcg32::new(7, 0);
+    let medium = fog(0.5);
+    // Straight through the middle is 2 units of fog, so exp(-1) of the rays get through.
+    let r = Ray::new(Point::new(0.0, 0.0, 5.0), Point::new(0.0, 0.0, -1.0));
+    let tries = 20000;
+    let through = (0..tries).filter(|_| medium.hit(r, 0.001, f32::INFINITY, &mut rng).is_none()).count();
+    let expected = (-1.0f32).exp();
+    assert!((through as f32 / tries as f32 - expected).abs() < 0.02, "{}", through);
+}
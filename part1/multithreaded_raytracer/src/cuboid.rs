@@ -0,0 +1,67 @@
+use std::sync::Arc;
+
+use rand::RngCore;
+
+use crate::{Point, Ray};
+use crate::aabb::AABB;
+use crate::hittable::{HitRecord, Hittable};
+use crate::hittable_list::{CheckHits, HittableList};
+use crate::material::Material;
+use crate::rect::{XYRect, XZRect, YZRect};
+
+// Axis-aligned box made of six rectangles sharing one material. Called Cuboid
+// so it doesn't shadow std's Box.
+pub struct Cuboid {
+    pub min: Point,
+    pub max: Point,
+    sides: HittableList,
+}
+
+impl Cuboid {
+    pub fn new(min: Point, max: Point, material: Arc<dyn Material>) -> Cuboid {
+        let mut sides = HittableList::new();
+        sides.add(XYRect::new(min.x, max.x, min.y, max.y, max.z, material.clone()));
+        sides.add(XYRect::new(min.x, max.x, min.y, max.y, min.z, material.clone()));
+        sides.add(XZRect::new(min.x, max.x, min.z, max.z, max.y, material.clone()));
+        sides.add(XZRect::new(min.x, max.x, min.z, max.z, min.y, material.clone()));
+        sides.add(YZRect::new(min.y, max.y, min.z, max.z, max.x, material.clone()));
+        sides.add(YZRect::new(min.y, max.y, min.z, max.z, min.x, material));
+        Cuboid {
+            min,
+            max,
+            sides,
+        }
+    }
+}
+
+impl Hittable for Cuboid {
+    fn hit(&self, r: Ray, t_min: f32, t_max: f32, rng: &mut dyn RngCore) -> Option<HitRecord<'_>> {
+        self.sides.get_hits(r, t_min, t_max, rng)
+    }
+
+    fn get_bounding_box(&self) -> Option<AABB> {
+        Some(AABB::new(self.min, self.max))
+    }
+}
+
+#[cfg(test)]
+#[test]
+fn nearest_face_is_hit_with_outward_normals() {
+    let mut rng = crate::random::Pcg32::new(7, 0);
+    let grey = Arc::new(crate::material::Lambertian::new(Point::new(0.5, 0.5, 0.5)));
+    let cuboid = Cuboid::new(Point::new(-1.0, 0.0, -2.0), Point::new(1.0, 3.0, 2.0), grey);
+
+    let rec = cuboid.hit(Ray::new(Point::new(0.0, 1.0, 10.0), Point::new(0.0, 0.0, -1.0)), 0.001, f32::INFINITY, &mut rng).unwrap();
+    assert!((rec.t - 8.0).abs() < 1e-5);
+    assert_eq!(rec.normal, Point::new(0.0, 0.0, 1.0));
+    assert!(rec.front_face);
+
+    let rec = cuboid.hit(Ray::new(Point::new(0.0, 1.0, 0.0), Point::new(1.0, 0.0, 0.0)), 0.001, f32::INFINITY, &mut rng).unwrap();
+    assert!((rec.t - 1.0).abs() < 1e-5);
+    assert!(!rec.front_face);
+
+    assert!(cuboid.hit(Ray::new(Point::new(0.0, 4.0, 10.0), Point::new(0.0, 0.0, -1.0)), 0.001, f32::INFINITY, &mut rng).is_none());
+    let bounding_box = cuboid.get_bounding_box().unwrap();
+    assert_eq!((bounding_box.min, bounding_box.max), (cuboid.min, cuboid.max));
+}
+
@@ -0,0 +1,61 @@
+use crate::image::Image;
+use crate::point::Point;
+use crate::row_data::RowData;
+use crate::tonemap::Tonemap;
+
+// Linear floating point framebuffer, rows stored top to bottom. Values are
+// radiance and can go well past 1.0.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Framebuffer {
+    pub width: u32,
+    pub height: u32,
+    pub pixels: Vec<Point>,
+}
+
+impl Framebuffer {
+    pub fn new(width: u32, height: u32) -> Framebuffer {
+        Framebuffer {
+            width,
+            height,
+            pixels: vec![Point::default(); width as usize * height as usize],
+        }
+    }
+
+    pub fn write_row(&mut self, row_data: &RowData) {
+        let i = row_data.index as usize;
+        self.pixels[i..i + row_data.colors.len()].copy_from_slice(&row_data.colors);
+    }
+
+    pub fn to_image(&self, tonemap: &Tonemap) -> Image {
+        let mut image = Image::new(self.width, self.height);
+        for (pixel, rgb) in self.pixels.iter().zip(image.data.chunks_mut(3)) {
+            rgb.copy_from_slice(&tonemap.to_rgb8(*pixel));
+        }
+        image
+    }
+}
+
+#[cfg(test)]
+#[test]
+fn rows_land_at_their_index() {
+    let mut framebuffer = Framebuffer::new(4, 3);
+    let mut row_data = RowData::new(4, 4);
+    for _ in 0..4 {
+        row_data.push_color(Point::new(1.0, 0.0, 1.0), 1);
+    }
+    framebuffer.write_row(&row_data);
+    let image = framebuffer.to_image(&Tonemap::default());
+    assert_eq!(image.data[..12], [0; 12]);
+    assert_eq!(image.data[12..24], [255, 0, 255, 255, 0, 255, 255, 0, 255, 255, 0, 255]);
+    assert_eq!(image.data[24..], [0; 12]);
+}
+
+#[test]
+fn keeps_radiance_above_one() {
+    let mut framebuffer = Framebuffer::new(1, 1);
+    let mut row_data = RowData::new(0, 1);
+    row_data.push_color(Point::new(8.0, 0.25, 0.0), 1);
+    framebuffer.write_row(&row_data);
+    assert_eq!(framebuffer.pixels[0], Point::new(8.0, 0.25, 0.0));
+    assert_eq!(framebuffer.to_image(&Tonemap::default()).data, [255, 137, 0]);
+}
@@ -0,0 +1,292 @@
+use std::fs;
+use std::io;
+use std::path::Path;
+
+use crate::framebuffer::Framebuffer;
+use crate::point::Point;
+
+// Scanlines this wide can use the run-length encoding, others are written flat.
+const MIN_RLE_WIDTH: u32 = 8;
+const MAX_RLE_WIDTH: u32 = 0x7fff;
+const MAX_RUN: usize = 127;
+
+fn invalid(message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, format!("bad HDR: {}", message))
+}
+
+// Shared-exponent encoding: three 8-bit mantissas scaled by 2^(e - 128).
+pub fn to_rgbe(color: Point) -> [u8; 4] {
+    let r = color[0].max(0.0);
+    let g = color[1].max(0.0);
+    let b = color[2].max(0.0);
+    let v = r.max(g).max(b);
+    if v.is_nan() || v < 1e-32 {
+        return [0, 0, 0, 0];
+    }
+    let v = v.min(f32::MAX);
+    let mut exponent = v.log2().floor() as i32 + 1;
+    // log2 can land one off either side of a power of two.
+    if v / 2f32.powi(exponent) >= 1.0 {
+        exponent += 1;
+    } else if v / 2f32.powi(exponent) < 0.5 {
+        exponent -= 1;
+    }
+    // Anything too bright for the largest exponent saturates rather than
+    // wrapping around to the zero exponent, which means black.
+    let exponent = exponent.min(127);
+    let scale = 256.0 / 2f32.powi(exponent);
+    let channel = |c: f32| (c.min(v) * scale).min(255.0) as u8;
+    [channel(r), channel(g), channel(b), (exponent + 128) as u8]
+}
+
+pub fn from_rgbe(rgbe: [u8; 4]) -> Point {
+    if rgbe[3] == 0 {
+        return Point::new(0.0, 0.0, 0.0);
+    }
+    let f = 2f32.powi(rgbe[3] as i32 - (128 + 8));
+    Point::new(rgbe[0] as f32 * f, rgbe[1] as f32 * f, rgbe[2] as f32 * f)
+}
+
+// Run-length encodes one channel of a scanline: runs of four or more equal
+// bytes become (128 + count, value), everything else is copied literally.
+fn write_channel(data: &[u8], out: &mut Vec<u8>) {
+    let mut cur = 0;
+    while cur < data.len() {
+        let mut run_start = cur;
+        let mut run_count = 0;
+        let mut old_run_count = 0;
+        while run_count < 4 && run_start < data.len() {
+            run_start += run_count;
+            old_run_count = run_count;
+            run_count = 1;
+            while run_start + run_count < data.len()
+                && run_count < MAX_RUN
+                && data[run_start] == data[run_start + run_count] {
+                run_count += 1;
+            }
+        }
+        // A short run right at the start is still worth a run code.
+        if old_run_count > 1 && old_run_count == run_start - cur {
+            out.extend_from_slice(&[128 + old_run_count as u8, data[cur]]);
+            cur = run_start;
+        }
+        while cur < run_start {
+            let literal = (run_start - cur).min(128);
+            out.push(literal as u8);
+            out.extend_from_slice(&data[cur..cur + literal]);
+            cur += literal;
+        }
+        if run_count >= 4 {
+            out.extend_from_slice(&[128 + run_count as u8, data[run_start]]);
+            cur += run_count;
+        }
+    }
+}
+
+fn read_channel(bytes: &[u8], position: &mut usize, out: &mut [u8]) -> io::Result<()> {
+    let mut x = 0;
+    while x < out.len() {
+        let code = *bytes.get(*position).ok_or_else(|| invalid("truncated scanline"))? as usize;
+        *position += 1;
+        if code > 128 {
+            let count = code - 128;
+            let value = *bytes.get(*position).ok_or_else(|| invalid("truncated scanline"))?;
+            *position += 1;
+            out.get_mut(x..x + count).ok_or_else(|| invalid("run overflows the scanline"))?.fill(value);
+            x += count;
+        } else {
+            if code == 0 {
+                return Err(invalid("empty literal run"));
+            }
+            let literal = bytes.get(*position..*position + code).ok_or_else(|| invalid("truncated scanline"))?;
+            out.get_mut(x..x + code).ok_or_else(|| invalid("run overflows the scanline"))?.copy_from_slice(literal);
+            *position += code;
+            x += code;
+        }
+    }
+    Ok(())
+}
+
+// Radiance RGBE (.hdr) file holding linear radiance.
+#[derive(Clone)]
+pub struct HDR {
+    pub framebuffer: Framebuffer,
+}
+
+impl HDR {
+    pub fn new(framebuffer: Framebuffer) -> HDR {
+        HDR {
+            framebuffer,
+        }
+    }
+
+    pub fn encode(&self) -> Vec<u8> {
+        let width = self.framebuffer.width;
+        let height = self.framebuffer.height;
+        let mut out = format!("#?RADIANCE\nFORMAT=32-bit_rle_rgbe\n\n-Y {} +X {}\n", height, width).into_bytes();
+        let rle = (MIN_RLE_WIDTH..=MAX_RLE_WIDTH).contains(&width);
+        let mut channel = Vec::with_capacity(width as usize);
+        for row in self.framebuffer.pixels.chunks(width.max(1) as usize) {
+            let rgbe: Vec<[u8; 4]> = row.iter().map(|&pixel| to_rgbe(pixel)).collect();
+            if !rle {
+                out.extend(rgbe.iter().flatten());
+                continue;
+            }
+            out.extend_from_slice(&[2, 2, (width >> 8) as u8, (width & 0xff) as u8]);
+            for c in 0..4 {
+                channel.clear();
+                channel.extend(rgbe.iter().map(|pixel| pixel[c]));
+                write_channel(&channel, &mut out);
+            }
+        }
+        out
+    }
+
+    pub fn write_file(&self, path: &Path) -> io::Result<()> {
+        fs::write(path, self.encode())
+    }
+
+    // Reads top-to-bottom files that are flat or use the run-length encoding
+    // above, which covers what most tools write.
+    pub fn decode(bytes: &[u8]) -> io::Result<HDR> {
+        let mut position = 0;
+        let mut next_line = || -> io::Result<&str> {
+            let end = bytes[position..].iter().position(|&b| b == b'\n').ok_or_else(|| invalid("truncated header"))?;
+            let line = std::str::from_utf8(&bytes[position..position + end]).map_err(|_| invalid("header is not text"))?;
+            position += end + 1;
+            Ok(line)
+        };
+
+        if !next_line()?.starts_with("#?") {
+            return Err(invalid("missing #? signature"));
+        }
+        loop {
+            let line = next_line()?;
+            if line.is_empty() {
+                break;
+            }
+            if let Some(format) = line.strip_prefix("FORMAT=") {
+                if format != "32-bit_rle_rgbe" {
+                    return Err(invalid(&format!("unsupported format {}", format)));
+                }
+            }
+        }
+        let resolution: Vec<&str> = next_line()?.split_whitespace().collect();
+        let (height, width) = match resolution[..] {
+            ["-Y", height, "+X", width] => (height.parse::<u32>(), width.parse::<u32>()),
+            _ => return Err(invalid("only -Y H +X W orientation is supported")),
+        };
+        let (height, width) = (height.map_err(|_| invalid("bad height"))?, width.map_err(|_| invalid("bad width"))?);
+
+        // Every scanline takes at least four bytes, and every pixel four when
+        // the width rules out run-length encoding, so check the data could be
+        // there before allocating for it.
+        let rle_width = (MIN_RLE_WIDTH..=MAX_RLE_WIDTH).contains(&width);
+        let pixels = (width as usize).checked_mul(height as usize);
+        let needed = if rle_width { (height as usize).checked_mul(4) } else { pixels.and_then(|n| n.checked_mul(4)) };
+        match (pixels, needed) {
+            (Some(_), Some(needed)) if needed <= bytes.len() - position => {}
+            _ => return Err(invalid("truncated pixel data")),
+        }
+        let mut framebuffer = Framebuffer::new(width, height);
+        let mut scanline = vec![0u8; width as usize * 4];
+        let mut channel = vec![0u8; width as usize];
+        for row in framebuffer.pixels.chunks_mut(width.max(1) as usize) {
+            let marker = bytes.get(position..position + 4).ok_or_else(|| invalid("truncated scanline"))?;
+            let rle = rle_width
+                && marker[0] == 2 && marker[1] == 2 && marker[2] & 0x80 == 0;
+            if rle {
+                if ((marker[2] as u32) << 8 | marker[3] as u32) != width {
+                    return Err(invalid("scanline width mismatch"));
+                }
+                position += 4;
+                for c in 0..4 {
+                    read_channel(bytes, &mut position, &mut channel)?;
+                    for (x, &value) in channel.iter().enumerate() {
+                        scanline[x * 4 + c] = value;
+                    }
+                }
+            } else {
+                let flat = bytes.get(position..position + scanline.len()).ok_or_else(|| invalid("truncated scanline"))?;
+                scanline.copy_from_slice(flat);
+                position += scanline.len();
+            }
+            for (pixel, rgbe) in row.iter_mut().zip(scanline.chunks(4)) {
+                *pixel = from_rgbe([rgbe[0], rgbe[1], rgbe[2], rgbe[3]]);
+            }
+        }
+        Ok(HDR { framebuffer })
+    }
+
+    pub fn read_file(path: &Path) -> io::Result<HDR> {
+        HDR::decode(&fs::read(path)?)
+    }
+}
+
+// RGBE keeps about 1% precision relative to the brightest channel.
+#[cfg(test)]
+fn close(a: Point, b: Point) -> bool {
+    let brightest = b[0].max(b[1]).max(b[2]);
+    (0..3).all(|c| (a[c] - b[c]).abs() <= brightest / 100.0)
+}
+
+#[test]
+fn rgbe_keeps_bright_values() {
+    assert_eq!(to_rgbe(Point::new(0.0, 0.0, 0.0)), [0, 0, 0, 0]);
+    assert_eq!(to_rgbe(Point::new(1.0, 0.5, 0.25)), [128, 64, 32, 129]);
+    for color in [Point::new(1000.0, 3.0, 0.5), Point::new(0.01, 0.02, 0.03), Point::new(255.0, 256.0, 257.0)] {
+        assert!(close(from_rgbe(to_rgbe(color)), color), "{:?}", color);
+    }
+    assert_eq!(to_rgbe(Point::new(-1.0, f32::NAN, 0.0)), [0, 0, 0, 0]);
+}
+
+#[test]
+fn rgbe_saturates_instead_of_wrapping() {
+    assert_eq!(to_rgbe(Point::new(f32::MAX, 0.0, 0.0)), [255, 0, 0, 255]);
+    assert_eq!(to_rgbe(Point::new(f32::INFINITY, 1.0, f32::MAX)), [255, 0, 255, 255]);
+    assert!(from_rgbe(to_rgbe(Point::new(f32::INFINITY, 0.0, 0.0)))[0] > 1e38);
+}
+
+#[test]
+fn round_trips_run_length_scanlines() {
+    let mut framebuffer = Framebuffer::new(40, 3);
+    for (i, pixel) in framebuffer.pixels.iter_mut().enumerate() {
+        // Long flat stretches mixed with noise to exercise both run kinds.
+        let value = if i % 40 < 20 { 2.0 } else { ((i * 7919) % 97) as f32 / 10.0 };
+        *pixel = Point::new(value, value * 0.5, 20.0);
+    }
+    let encoded = HDR::new(framebuffer.clone()).encode();
+    assert!(encoded.starts_with(b"#?RADIANCE\n"));
+    let decoded = HDR::decode(&encoded).unwrap().framebuffer;
+    assert_eq!((decoded.width, decoded.height), (40, 3));
+    for (a, b) in decoded.pixels.iter().zip(&framebuffer.pixels) {
+        assert!(close(*a, *b), "{:?} != {:?}", a, b);
+    }
+}
+
+#[test]
+fn round_trips_narrow_images_flat() {
+    let mut framebuffer = Framebuffer::new(3, 2);
+    framebuffer.pixels[4] = Point::new(64.0, 0.0, 1.0);
+    let encoded = HDR::new(framebuffer.clone()).encode();
+    let header_len = encoded.len() - 3 * 2 * 4;
+    assert_eq!(&encoded[header_len - 10..header_len], b"-Y 2 +X 3\n");
+    let decoded = HDR::decode(&encoded).unwrap().framebuffer;
+    assert!(close(decoded.pixels[4], framebuffer.pixels[4]));
+}
+
+#[test]
+fn rejects_truncated_files() {
+    let encoded = HDR::new(Framebuffer::new(16, 4)).encode();
+    assert!(HDR::decode(&encoded[..encoded.len() - 1]).is_err());
+    assert!(HDR::decode(b"P6 1 1 255\n").is_err());
+}
+
+#[test]
+fn rejects_sizes_larger_than_the_file() {
+    for resolution in ["-Y 4294967295 +X 4294967295", "-Y 4294967295 +X 100", "-Y 1000 +X 4"] {
+        let mut bytes = format!("#?RADIANCE\nFORMAT=32-bit_rle_rgbe\n\n{}\n", resolution).into_bytes();
+        bytes.extend_from_slice(&[2, 2, 0, 100, 1, 1, 1, 1]);
+        assert!(HDR::decode(&bytes).is_err(), "{}", resolution);
+    }
+}
@@ -1,25 +1,42 @@
-use rand::prelude::ThreadRng;
+use std::sync::Arc;
+
+use rand::RngCore;
 
 use crate::{dot, Point, Ray};
 use crate::aabb::AABB;
-use crate::scatter_results::ScatterResults;
+use crate::material::Material;
 
 #[derive(Clone, Copy)]
-pub struct HitRecord {
+pub struct HitRecord<'a> {
     pub p: Point,
     pub normal: Point,
     pub t: f32,
     pub front_face: bool,
-    pub scatter_results: Option<ScatterResults>,
+    // Surface coordinates for texturing, usually in 0..1.
+    pub u: f32,
+    pub v: f32,
+    // Weights of the second and third vertex for triangle hits, zero otherwise.
+    pub barycentric: (f32, f32),
+    pub material: &'a dyn Material,
 }
 
-pub trait Hittable : Send {
-    fn hit(&self, r: Ray, t_min: f32, t_max: f32, rng: &mut ThreadRng) -> Option<HitRecord>;
-    fn scatter(&self, r_in: Ray, rec: &HitRecord, rng: &mut ThreadRng) -> Option<ScatterResults>;
+pub trait Hittable : Send + Sync {
+    fn hit(&self, r: Ray, t_min: f32, t_max: f32, rng: &mut dyn RngCore) -> Option<HitRecord<'_>>;
     fn get_bounding_box(&self) -> Option<AABB>;
 }
 
-impl HitRecord {
+// Lets a shared object go into a HittableList without copying it.
+impl<T: Hittable + ?Sized> Hittable for Arc<T> {
+    fn hit(&self, r: Ray, t_min: f32, t_max: f32, rng: &mut dyn RngCore) -> Option<HitRecord<'_>> {
+        self.as_ref().hit(r, t_min, t_max, rng)
+    }
+
+    fn get_bounding_box(&self) -> Option<AABB> {
+        self.as_ref().get_bounding_box()
+    }
+}
+
+impl HitRecord<'_> {
     pub fn set_face_normal(&mut self, r: Ray, outward_normal: Point) {
         self.front_face = dot(r.direction, outward_normal) < 0.0;
         if self.front_face {
@@ -28,4 +45,4 @@ impl HitRecord {
             self.normal = -outward_normal;
         }
     }
-}
\ No newline at end of file
+}
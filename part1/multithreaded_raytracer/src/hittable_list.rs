@@ -1,4 +1,6 @@
-use rand::rngs::ThreadRng;
+use std::sync::OnceLock;
+
+use rand::RngCore;
 
 use crate::bvh::BVH;
 use crate::hittable::{HitRecord, Hittable};
@@ -6,42 +8,175 @@ use crate::ray::Ray;
 
 pub struct HittableList
 {
-    hittable_list: Vec<Box<dyn Hittable + Send + Sync>>,
-    pub bvh: BVH,
+    hittable_list: Vec<Box<dyn Hittable>>,
+    unbounded: Vec<usize>,
+    bvh: OnceLock<BVH>,
 }
 
 pub trait CheckHits : Send {
-    fn get_hits(&self, r: Ray, t_min: f32, t_max: f32, rng: &mut ThreadRng) -> Option<HitRecord>;
+    fn get_hits(&self, r: Ray, t_min: f32, t_max: f32, rng: &mut dyn RngCore) -> Option<HitRecord<'_>>;
 }
 
 impl HittableList {
     pub fn new() -> Self {
         HittableList {
             hittable_list: Vec::new(),
-            bvh: BVH::new(),
+            unbounded: Vec::new(),
+            bvh: OnceLock::new(),
         }
     }
 
-    pub fn add<U: Hittable + 'static + Send + Sync>(&mut self, o: U) {
-        if let Some(bounding_box) = o.get_bounding_box() {
-            self.bvh.add(bounding_box);
+    pub fn add<U: Hittable + 'static>(&mut self, o: U) {
+        if o.get_bounding_box().is_none() {
+            self.unbounded.push(self.hittable_list.len());
         }
         self.hittable_list.push(Box::new(o));
+        self.bvh = OnceLock::new();
+    }
+
+    // The tree is built on the first query after the last add, so every render
+    // thread shares one copy.
+    pub fn bvh(&self) -> &BVH {
+        self.bvh.get_or_init(|| {
+            let boxes: Vec<_> = self.hittable_list.iter()
+                .enumerate()
+                .filter_map(|(i, o)| o.get_bounding_box().map(|b| (i, b)))
+                .collect();
+            BVH::new(&boxes)
+        })
+    }
+}
+
+impl Default for HittableList {
+    fn default() -> Self {
+        HittableList::new()
     }
 }
 
 impl CheckHits for HittableList {
-    fn get_hits(&self, r: Ray, t_min: f32, t_max: f32, rng: &mut ThreadRng) -> Option<HitRecord> {
-        let mut _hit_anything = false;
-        let mut _closest_so_far = t_max;
-        for object in &self.hittable_list {
-            if let Some(mut rec) = object.hit(r, t_min, t_max, rng) {
-                _hit_anything = true;
-                _closest_so_far = rec.t.clone();
-                rec.scatter_results = object.scatter(r, &rec, rng);
-                return Some(rec);
+    fn get_hits(&self, r: Ray, t_min: f32, t_max: f32, rng: &mut dyn RngCore) -> Option<HitRecord<'_>> {
+        let mut closest = self.bvh().hit(r, t_min, t_max, |i, closest_so_far| {
+            self.hittable_list[i].hit(r, t_min, closest_so_far, rng)
+        });
+        for &i in &self.unbounded {
+            let closest_so_far = closest.map_or(t_max, |(_, rec)| rec.t);
+            if let Some(rec) = self.hittable_list[i].hit(r, t_min, closest_so_far, rng) {
+                closest = Some((i, rec));
             }
         }
-        return None;
+        closest.map(|(_, rec)| rec)
+    }
+}
+
+// Renders the albedo of whatever each primary ray hits first. A pinhole camera
+// and diffuse spheres keep this independent of the random numbers drawn.
+#[cfg(test)]
+fn render_albedo(world: &HittableList, width: u32, height: u32) -> Vec<crate::point::Point> {
+    use crate::camera::{Camera, Cast};
+    use crate::point::Point;
+    use crate::sampler::SamplerKind;
+
+    let mut sampler = SamplerKind::Independent.create(0, 1);
+    let camera = Camera::new(Point::new(0.0, 0.0, 6.0), Point::new(0.0, 0.0, 0.0), Point::new(0.0, 1.0, 0.0),
+                             60.0, width as f32 / height as f32, 0.0, 6.0);
+    let mut pixels = Vec::new();
+    for j in 0..height {
+        for i in 0..width {
+            let u = (i as f32 + 0.5) / width as f32;
+            let v = (j as f32 + 0.5) / height as f32;
+            let r = camera.get_ray(u, v, sampler.as_mut());
+            let color = match world.get_hits(r, 0.001, f32::INFINITY, sampler.rng()) {
+                Some(rec) => rec.material.scatter(r, &rec, sampler.as_mut()).unwrap().attenuation,
+                None => Point::default(),
+            };
+            pixels.push(color);
+        }
+    }
+    pixels
+}
+
+#[cfg(test)]
+fn diffuse_sphere(center: crate::point::Point, radius: f32, albedo: crate::point::Point) -> crate::sphere::Sphere {
+    use std::sync::Arc;
+    use crate::material::Lambertian;
+    use crate::sphere::Sphere;
+
+    Sphere::new(center, radius, Arc::new(Lambertian::new(albedo)))
+}
+
+#[cfg(test)]
+fn overlapping_spheres() -> Vec<crate::sphere::Sphere> {
+    use crate::point::Point;
+
+    vec![
+        diffuse_sphere(Point::new(0.0, 0.0, 0.0), 1.0, Point::new(1.0, 0.0, 0.0)),
+        diffuse_sphere(Point::new(0.5, 0.2, -1.0), 1.2, Point::new(0.0, 1.0, 0.0)),
+        diffuse_sphere(Point::new(-0.4, -0.3, 1.0), 0.6, Point::new(0.0, 0.0, 1.0)),
+        diffuse_sphere(Point::new(0.0, 0.0, -3.0), 2.5, Point::new(1.0, 1.0, 0.0)),
+        diffuse_sphere(Point::new(0.9, 0.9, 0.5), 0.5, Point::new(0.0, 1.0, 1.0)),
+    ]
+}
+
+#[cfg(test)]
+fn world_from(spheres: &[crate::sphere::Sphere]) -> HittableList {
+    let mut world = HittableList::new();
+    for s in spheres {
+        world.add(s.clone());
+    }
+    world
+}
+
+#[test]
+fn nearer_sphere_wins_in_either_order() {
+    use crate::point::Point;
+
+    let red = Point::new(1.0, 0.0, 0.0);
+    let near = diffuse_sphere(Point::new(0.0, 0.0, -2.0), 0.5, red);
+    let far = diffuse_sphere(Point::new(0.0, 0.0, -5.0), 0.5, Point::new(0.0, 1.0, 0.0));
+    let r = Ray::new(Point::default(), Point::new(0.0, 0.0, -1.0));
+    let mut rng = crate::random::Pcg32::new(7, 0);
+
+    for world in [world_from(&[near.clone(), far.clone()]), world_from(&[far, near])] {
+        let rec = world.get_hits(r, 0.001, f32::INFINITY, &mut rng).unwrap();
+        assert!((rec.t - 1.5).abs() < 1e-5);
+        let mut sampler = crate::sampler::SamplerKind::Independent.create(0, 1);
+        assert_eq!(rec.material.scatter(r, &rec, sampler.as_mut()).unwrap().attenuation, red);
+    }
+}
+
+#[test]
+fn overlapping_spheres_render_the_same_in_every_insertion_order() {
+    let spheres = overlapping_spheres();
+    let expected = render_albedo(&world_from(&spheres), 48, 27);
+
+    let mut order: Vec<usize> = (0..spheres.len()).collect();
+    for _ in 0..spheres.len() {
+        order.rotate_left(1);
+        let rotated: Vec<_> = order.iter().map(|&i| spheres[i].clone()).collect();
+        assert!(render_albedo(&world_from(&rotated), 48, 27) == expected);
+
+        let reversed: Vec<_> = rotated.into_iter().rev().collect();
+        assert!(render_albedo(&world_from(&reversed), 48, 27) == expected);
     }
-}
\ No newline at end of file
+}
+
+#[test]
+fn shuffled_sphere_field_renders_the_same() {
+    use rand::Rng;
+    use rand::seq::SliceRandom;
+    use crate::sphere::Sphere;
+    use crate::utility::random_point_range;
+
+    let mut rng = crate::random::Pcg32::new(7, 0);
+    let mut spheres: Vec<Sphere> = (0..300)
+        .map(|_| diffuse_sphere(random_point_range(&mut rng, -3.0, 3.0),
+                                rng.gen_range(0.1..0.6),
+                                random_point_range(&mut rng, 0.0, 1.0)))
+        .collect();
+    let expected = render_albedo(&world_from(&spheres), 48, 27);
+
+    for _ in 0..4 {
+        spheres.shuffle(&mut rng);
+        assert!(render_albedo(&world_from(&spheres), 48, 27) == expected);
+    }
+}
@@ -0,0 +1,17 @@
+// 8-bit RGB framebuffer, rows stored top to bottom.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Image {
+    pub width: u32,
+    pub height: u32,
+    pub data: Vec<u8>,
+}
+
+impl Image {
+    pub fn new(width: u32, height: u32) -> Image {
+        Image {
+            width,
+            height,
+            data: vec![0; width as usize * height as usize * 3],
+        }
+    }
+}
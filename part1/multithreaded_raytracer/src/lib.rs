@@ -0,0 +1,82 @@
+// The following is based heavily on Peter Shirley's  <ptrshrl@gmail.com>
+// Ray Tracing in One Weekend
+// https://raytracing.github.io/books/RayTracingInOneWeekend.html
+
+//! Offline ray tracer. Build a `Scene` (by hand or with `Scene::parse`), pick
+//! `RenderSettings` and call `render` to get an `Image` back.
+
+#![allow(clippy::upper_case_acronyms)]
+
+pub mod hittable;
+pub mod hittable_list;
+pub mod camera;
+pub mod utility;
+pub mod point;
+pub mod ray;
+pub mod ppm;
+pub mod png;
+pub mod zlib;
+pub mod performance_stats;
+pub mod material;
+pub mod sphere;
+pub mod moving_sphere;
+pub mod rect;
+pub mod plane;
+pub mod cuboid;
+pub mod constant_medium;
+pub mod triangle;
+pub mod mesh;
+pub mod matrix;
+pub mod transform;
+pub mod texture;
+pub mod perlin;
+pub mod random;
+pub mod sampler;
+pub mod aabb;
+pub mod bvh;
+pub mod row_data;
+pub mod scatter_results;
+pub mod scene;
+pub mod sky;
+pub mod output;
+pub mod render_settings;
+pub mod image;
+pub mod framebuffer;
+pub mod accumulator;
+pub mod checkpoint;
+pub mod hdr;
+pub mod tonemap;
+pub mod tiles;
+pub mod render;
+
+pub use crate::accumulator::Accumulator;
+pub use crate::camera::{Camera, Cast};
+pub use crate::checkpoint::Checkpoint;
+pub use crate::constant_medium::ConstantMedium;
+pub use crate::cuboid::Cuboid;
+pub use crate::framebuffer::Framebuffer;
+pub use crate::hittable::{HitRecord, Hittable};
+pub use crate::hittable_list::{CheckHits, HittableList};
+pub use crate::image::Image;
+pub use crate::material::{Dielectric, DiffuseLight, Isotropic, Lambertian, Material, Metal};
+pub use crate::matrix::Matrix4;
+pub use crate::mesh::Mesh;
+pub use crate::moving_sphere::MovingSphere;
+pub use crate::plane::Plane;
+pub use crate::perlin::Perlin;
+pub use crate::point::Point;
+pub use crate::random::Pcg32;
+pub use crate::ray::Ray;
+pub use crate::rect::{XYRect, XZRect, YZRect};
+pub use crate::render::{render, render_hdr, render_hdr_with_progress, remaining_samples, render_pass, render_progressive, render_with_progress};
+pub use crate::render_settings::RenderSettings;
+pub use crate::sampler::{Sampler, SamplerKind};
+pub use crate::scene::{Scene, SceneError};
+pub use crate::sky::Sky;
+pub use crate::sphere::Sphere;
+pub use crate::texture::{Checker, ImageTexture, NoiseTexture, Pattern, SolidColor, Texture, Wrap};
+pub use crate::tonemap::{Operator, Tonemap};
+pub use crate::transform::Transform;
+pub use crate::triangle::Triangle;
+
+use crate::utility::dot;
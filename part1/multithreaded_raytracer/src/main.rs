@@ -1,155 +1,195 @@
-// The following is based heavily on Peter Shirley's  <ptrshrl@gmail.com>
-// Ray Tracing in One Weekend
-// https://raytracing.github.io/books/RayTracingInOneWeekend.html
+use std::env;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::process;
+use std::time::{Duration, Instant};
+
+use final_project::output::OutputFormat;
+use final_project::hdr::HDR;
+use final_project::png::PNG;
+use final_project::ppm::PPM;
+use final_project::checkpoint::scene_hash;
+use final_project::tonemap::Tonemap;
+use final_project::{remaining_samples, render_progressive, Accumulator, Checkpoint, Framebuffer, RenderSettings, Scene, SceneError};
+use progress_bar::*;
 
-use std::thread;
+use crate::cli::{Command, Options};
 
-use progress_bar::*;
-use rand::Rng;
-use rand::rngs::ThreadRng;
-
-use crate::camera::{Camera, Cast};
-use crate::hittable_list::{CheckHits, HittableList};
-use crate::point::Point;
-use crate::ppm::PPM;
-use crate::ray::Ray;
-use crate::row_data::RowData;
-use crate::sphere::{DielectricSphere, LambertianSphere, MetalSphere};
-use crate::utility::{dot, random_unit_vector, unit_vector};
-
-mod hittable;
-mod hittable_list;
-mod camera;
-mod utility;
-mod point;
-mod ray;
-mod ppm;
-mod performance_stats;
-mod material;
-mod sphere;
-mod aabb;
-mod bvh;
-mod row_data;
-mod scatter_results;
-
-const ASPECT_RATIO: f32 = 16.0 / 9.0;
-const IMAGE_WIDTH: u32 = 1600;
-const IMAGE_HEIGHT: u32 = (IMAGE_WIDTH as f32 / ASPECT_RATIO) as u32;
-const IMAGE_SIZE: usize = (IMAGE_HEIGHT * IMAGE_WIDTH * 3) as usize;
-
-fn get_canvas_color(r: Ray) -> Point {
-
-    let unit_direction = unit_vector(r.direction);
-    let t = 1.6 * (unit_direction.y + 1.0);
-    return (1.0 - t) * Point::new(0.9, 0.9, 0.9) + t * Point::new(0.5, 0.7, 1.0);
-}
+mod cli;
 
-fn row_color(row_j: u32, w: &HittableList, c: &Camera, depth: i32, samples_per_pixel: i32) -> RowData {
-    let image_rgb_i = row_j * IMAGE_WIDTH * 3;
-    let mut rng = rand::thread_rng();
-    let mut row_data = RowData::new(image_rgb_i);
-
-    for pixel_i in 0..IMAGE_WIDTH {
-        let mut color = Point::new(0.0, 0.0, 0.0);
-        for _ in 0..samples_per_pixel {
-            let u = (pixel_i as f32 + rng.gen_range(0.0..1.0)) / (IMAGE_WIDTH - 1) as f32;
-            let v = (row_j as f32 + rng.gen_range(0.0..1.0)) / (IMAGE_HEIGHT - 1) as f32;
-            let r = c.get_ray(u, v, &mut rng);
-            let sample = ray_color(r, w, depth, &mut rng);
-            color = color + sample;
+const DEFAULT_SCENE: &str = include_str!("../scenes/three_spheres.scene");
+
+// Command line options override the scene's own render settings.
+fn apply_options(mut settings: RenderSettings, options: &Options) -> Result<RenderSettings, String> {
+    let aspect_ratio = settings.aspect_ratio();
+    match (options.width, options.height) {
+        (Some(width), Some(height)) => {
+            settings.width = width;
+            settings.height = height;
+        }
+        (Some(width), None) => {
+            settings.width = width;
+            settings.height = (width as f32 / aspect_ratio).round() as u32;
         }
-        row_data.push_color(color, samples_per_pixel);
-        inc_progress_bar();
+        (None, Some(height)) => {
+            settings.height = height;
+            settings.width = (height as f32 * aspect_ratio).round() as u32;
+        }
+        (None, None) => {}
+    }
+    if settings.width == 0 || settings.height == 0 {
+        return Err(format!("a {}x{} image has no pixels", settings.width, settings.height));
     }
-    row_data
+    settings.samples_per_pixel = options.samples_per_pixel.unwrap_or(settings.samples_per_pixel);
+    settings.depth = options.depth.unwrap_or(settings.depth);
+    settings.threads = options.threads.unwrap_or(settings.threads);
+    settings.tonemap.operator = options.tonemap.unwrap_or(settings.tonemap.operator);
+    settings.tonemap.exposure = options.exposure.unwrap_or(settings.tonemap.exposure);
+    settings.seed = options.seed.unwrap_or(settings.seed);
+    settings.sampler = options.sampler.unwrap_or(settings.sampler);
+    settings.adaptive = options.adaptive.or(settings.adaptive);
+    Ok(settings)
 }
 
-fn ray_color(r: Ray, world: &HittableList, depth: i32, rng: &mut ThreadRng) -> Point {
-    if depth <= 0 { return Point::new(0.0, 0.0, 0.0); }
-    if let Some(rec) = world.get_hits(r, 0.001, f32::INFINITY, rng) {
-        if let Some(scatter_results) = rec.scatter_results {
-            let scattered_ray = scatter_results.ray_dir;
-            let attenuation = scatter_results.attenuation;
-            return attenuation * ray_color(scattered_ray, world, depth - 1, rng);
-        } else {
-            return Point::new(0.0, 0.0, 0.0);
-        }
+fn write_output(framebuffer: Framebuffer, path: &Path, format: OutputFormat, tonemap: &Tonemap) -> io::Result<()> {
+    match format {
+        OutputFormat::Ppm => PPM::new(framebuffer.to_image(tonemap)).write_file(path),
+        OutputFormat::Png => PNG::new(framebuffer.to_image(tonemap)).write_file(path),
+        OutputFormat::Hdr => HDR::new(framebuffer).write_file(path),
     }
-    return get_canvas_color(r);
+}
+
+// Writes next to `path` first and renames over it, so a render stopped while
+// writing a preview still leaves the previous one readable.
+fn write_preview(framebuffer: Framebuffer, path: &Path, format: OutputFormat, tonemap: &Tonemap) -> io::Result<()> {
+    let mut partial = path.as_os_str().to_owned();
+    partial.push(".partial");
+    let partial = PathBuf::from(partial);
+    write_output(framebuffer, &partial, format, tonemap)?;
+    fs::rename(&partial, path)
+}
+
+// Picks up the sums saved by an earlier run, as long as they belong to the
+// same scene and seed.
+fn resume(path: &Path, scene_hash: u64, seed: u64) -> Result<Checkpoint, String> {
+    let checkpoint = Checkpoint::read_file(path).map_err(|err| format!("{}: {}", path.display(), err))?;
+    if checkpoint.seed != seed {
+        return Err(format!("{}: saved with seed {}, pass --seed {} to resume it", path.display(), checkpoint.seed, checkpoint.seed));
+    }
+    if checkpoint.scene_hash != scene_hash {
+        return Err(format!("{}: saved from a different scene, image size, depth or sampler, delete it to start over", path.display()));
+    }
+    Ok(checkpoint)
+}
+
+fn fail(message: String) -> ! {
+    eprintln!("error: {}", message);
+    eprintln!();
+    eprintln!("Run with --help for usage.");
+    process::exit(2);
 }
 
 fn main() -> std::io::Result<()> {
 
+    // options
 
-    // image
+    let options = match cli::parse_args(env::args().skip(1)) {
+        Ok(Command::Render(options)) => options,
+        Ok(Command::Help) => {
+            print!("{}", cli::USAGE);
+            return Ok(());
+        }
+        Err(message) => fail(message),
+    };
 
-    let samples_per_pixel = 300;
-    let depth = 500;
+    // scene
+
+    let loaded = match &options.scene {
+        Some(path) => fs::read_to_string(path).map_err(SceneError::Io)
+            .and_then(|source| Ok((Scene::parse_in(&source, path.parent().unwrap_or(Path::new("")))?, source)))
+            .map_err(|err| format!("{}: {}", path.display(), err)),
+        None => Scene::parse(DEFAULT_SCENE)
+            .map(|scene| (scene, DEFAULT_SCENE.to_owned()))
+            .map_err(|err| format!("default scene: {}", err)),
+    };
+    let (scene, source) = match loaded {
+        Ok(loaded) => loaded,
+        Err(message) => {
+            eprintln!("{}", message);
+            process::exit(1);
+        }
+    };
+    let mut settings = match apply_options(scene.settings, &options) {
+        Ok(settings) => settings,
+        Err(message) => fail(message),
+    };
+
+    // checkpoint
+
+    let scene_hash = scene_hash(&source, &settings);
+    let accumulator = match &options.checkpoint {
+        Some(path) if path.exists() => match resume(path, scene_hash, settings.seed) {
+            Ok(checkpoint) => {
+                settings.strata = Some(checkpoint.strata);
+                checkpoint.accumulator
+            }
+            Err(message) => {
+                eprintln!("{}", message);
+                process::exit(1);
+            }
+        },
+        _ => Accumulator::new(settings.width, settings.height),
+    };
+    let save_checkpoint = |accumulator: &Accumulator| match &options.checkpoint {
+        Some(path) => Checkpoint::new(settings.seed, scene_hash, settings.strata_count(), accumulator.clone()).write_file(path),
+        None => Ok(()),
+    };
 
     // progress bar
-    init_progress_bar(IMAGE_SIZE / 3 as usize);
+    let samples_done = accumulator.samples_done();
+    init_progress_bar(remaining_samples(&accumulator, &settings));
     set_progress_bar_action("Loading", Color::Blue, Style::Bold);
+    if samples_done > 0 {
+        print_progress_bar_info("Resuming", &format!("from {} samples per pixel", samples_done), Color::Blue, Style::Normal);
+    }
 
-    // world
-
-    let mut world = HittableList::new();
-
-
-    let a = Point::new(0.0, 1.0, 0.0);
-    let pink = Point::new(1.0, 0.8, 0.801);
-    let sphere1 = MetalSphere::new(a, 1.0, pink, 0.0);
-    world.add(sphere1);
-
-    let b = Point::new(-2.0, 1.0, 0.0);
-    let sphere2 = DielectricSphere::new(b, 1.0, 1.5);
-    world.add(sphere2);
-
-    let c = Point::new(2.0, 1.0, 0.0);
-    let red = Point::new(1.0, 0.0, 0.0);
-    let sphere3 = LambertianSphere::new(c, 1.0, red);
-    world.add(sphere3);
-    let w = &world;
-
-    // CAMERA
-    let lookfrom = Point::new(0.0, 2.0, 3.0);
-    let lookat = Point::new(0.0, 0.0, 0.0);
-    let vup = Point::new(0.0, 1.0, 0.0);
-    let dist_to_focus = 10.0;
-    let aperture = 0.1;
-
-    let camera = Camera::new(lookfrom, lookat, vup, 90.0, ASPECT_RATIO, aperture, dist_to_focus);
-    let c = &camera;
-
-    let filename = "output.ppm".to_owned();
-    let mut image_rgb: [u8; IMAGE_SIZE] = [0; IMAGE_SIZE];
-    thread::scope(|s| {
-        let mut handles = vec![];
-        for row_j in 0..IMAGE_HEIGHT {
-            let handle = s.spawn(move || {
-                row_color(row_j, w, c, depth, samples_per_pixel)
-            });
-            handles.push(handle);
+    // previews and checkpoints along the way, every minute by default when
+    // there is a checkpoint to keep
+    let preview_seconds = match (options.preview_passes, options.preview_seconds, &options.checkpoint) {
+        (None, None, Some(_)) => Some(60.0),
+        (_, seconds, _) => seconds,
+    };
+    let mut last_preview = Instant::now();
+    let on_pass = |accumulator: &Accumulator, passes_done: u32| {
+        let every_passes = options.preview_passes.is_some_and(|n| passes_done.is_multiple_of(n));
+        let every_seconds = preview_seconds.is_some_and(|s| last_preview.elapsed() >= Duration::from_secs_f32(s));
+        if !(every_passes || every_seconds) {
+            return;
         }
-        for handle in handles {
-            let row_data = handle.join().unwrap();
-            let mut i = row_data.index as usize;
-            for val in row_data.rbg_values {
-                image_rgb[i] = val;
-                i += 1;
-            }
+        let saved = write_preview(accumulator.to_framebuffer(), &options.output, options.format, &settings.tonemap)
+            .and_then(|()| save_checkpoint(accumulator));
+        match saved {
+            Ok(()) => print_progress_bar_info("Preview", &format!("after pass {}", passes_done), Color::Green, Style::Normal),
+            Err(err) => print_progress_bar_info("Warning", &format!("couldn't write preview: {}", err), Color::Yellow, Style::Normal),
         }
-    });
-
-
-    let image = PPM {
-        height: IMAGE_HEIGHT,
-        width: IMAGE_WIDTH,
-        data: Vec::from(image_rgb),
+        last_preview = Instant::now();
     };
-    image.write_file(&filename).expect("Failed to write to PPM.");
+
+    let accumulator = render_progressive(&scene, &settings, accumulator, options.pass_samples,
+                                         |done, _| set_progress_bar_progression(done), on_pass);
+
+    write_output(accumulator.to_framebuffer(), &options.output, options.format, &settings.tonemap).expect("Failed to write the image.");
+    save_checkpoint(&accumulator).expect("Failed to write the checkpoint.");
+    if let Some(path) = &options.heatmap {
+        let heatmap = accumulator.sample_heatmap();
+        match OutputFormat::from_path(path) {
+            Some(OutputFormat::Png) => PNG::new(heatmap).write_file(path),
+            _ => PPM::new(heatmap).write_file(path),
+        }.expect("Failed to write the heatmap.");
+    }
     finalize_progress_bar();
 
 
     Ok(())
 }
-
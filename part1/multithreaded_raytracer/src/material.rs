@@ -1,24 +1,196 @@
-use rand::prelude::ThreadRng;
-use crate::{Point, random_unit_vector, Ray};
+use std::sync::Arc;
+
+use num::pow;
+
+use crate::{Point, Ray};
 use crate::hittable::HitRecord;
+use crate::sampler::Sampler;
+use crate::scatter_results::ScatterResults;
+use crate::texture::{SolidColor, Texture};
+use crate::utility::{dot, reflect, refract, sample_in_unit_sphere, sample_unit_vector, unit_vector};
 
-pub trait Material {
-    fn scatter(&self, normal: Point, rec: &HitRecord, rng: &mut ThreadRng) -> Option<(Ray, Point)>;
+// How light leaves a surface. Geometry holds an Arc<dyn Material> so any shape
+// can share any material.
+pub trait Material: Send + Sync {
+    fn scatter(&self, r_in: Ray, rec: &HitRecord, sampler: &mut dyn Sampler) -> Option<ScatterResults>;
+
+    // Radiance the surface gives off by itself at the hit point.
+    fn emitted(&self, _rec: &HitRecord) -> Point {
+        Point::new(0.0, 0.0, 0.0)
+    }
 }
 
-pub struct Lambertian{
-    pub albedo: Point
+pub struct Lambertian {
+    pub albedo: Arc<dyn Texture>,
+}
+
+impl Lambertian {
+    pub fn new(albedo: Point) -> Lambertian {
+        Lambertian::textured(Arc::new(SolidColor::new(albedo)))
+    }
+
+    pub fn textured(albedo: Arc<dyn Texture>) -> Lambertian {
+        Lambertian {
+            albedo,
+        }
+    }
 }
 
 impl Material for Lambertian {
-    fn scatter(&self, normal: Point, rec: &HitRecord, rng: &mut ThreadRng) -> Option<(Ray, Point)> {
-        let mut scatter_direction = normal + random_unit_vector(rng);
-        if scatter_direction.near_zero() {
-            scatter_direction = normal;
+    fn scatter(&self, r_in: Ray, rec: &HitRecord, sampler: &mut dyn Sampler) -> Option<ScatterResults> {
+        let mut scatter_direction = rec.normal + sample_unit_vector(sampler.next_2d());
+        if scatter_direction.near_zero() { scatter_direction = rec.normal; }
+        let scattered = Ray::with_time(rec.p, scatter_direction, r_in.time);
+        Some(
+            ScatterResults {
+                ray_dir: scattered,
+                norm: rec.normal,
+                attenuation: self.albedo.value(rec.u, rec.v, rec.p),
+            }
+        )
+    }
+}
+
+pub struct Metal {
+    pub albedo: Arc<dyn Texture>,
+    pub fuzz: f32,
+}
+
+impl Metal {
+    pub fn new(albedo: Point, fuzz: f32) -> Metal {
+        Metal::textured(Arc::new(SolidColor::new(albedo)), fuzz)
+    }
+
+    pub fn textured(albedo: Arc<dyn Texture>, fuzz: f32) -> Metal {
+        Metal {
+            albedo,
+            fuzz,
+        }
+    }
+}
+
+impl Material for Metal {
+    fn scatter(&self, r_in: Ray, rec: &HitRecord, sampler: &mut dyn Sampler) -> Option<ScatterResults> {
+        let reflected = reflect(unit_vector(r_in.direction), rec.normal);
+        // Reflection Requirement
+        let fuzz = sample_in_unit_sphere(sampler.next_2d(), sampler.next_1d());
+        let scattered = Ray::with_time(rec.p, reflected + self.fuzz * fuzz, r_in.time);
+        let attenuation = self.albedo.value(rec.u, rec.v, rec.p);
+        if dot(scattered.direction, rec.normal) > 0.0 {
+            return Some(
+                ScatterResults {
+                    ray_dir: scattered,
+                    norm: rec.normal,
+                    attenuation,
+                }
+            );
+        }
+        None
+    }
+}
+
+pub struct Dielectric {
+    pub ir: f32,
+}
+
+impl Dielectric {
+    pub fn new(ir: f32) -> Dielectric {
+        Dielectric {
+            ir,
+        }
+    }
+
+    fn reflectance(cosine: f32, ref_idx: f32) -> f32 {
+        let mut r0 = (1.0 - ref_idx) / (1.0 + ref_idx);
+        r0 = r0 * r0;
+        r0 + (1.0 - r0) * pow(1.0 - cosine, 5)
+    }
+}
+
+impl Material for Dielectric {
+    fn scatter(&self, r_in: Ray, rec: &HitRecord, sampler: &mut dyn Sampler) -> Option<ScatterResults> {
+        let refraction_ratio = if rec.front_face { 1.0 / self.ir } else { self.ir };
+        let attenuation = Point::new(1.0, 1.0, 1.0);
+        let unit_direction = unit_vector(r_in.direction);
+        let dot = dot(-unit_direction, rec.normal);
+        let cos_theta = if dot < 1.0 { dot } else { 1.0 };
+
+        let sin_theta = (1.0 - cos_theta * cos_theta).sqrt();
+        let cannot_refract = refraction_ratio * sin_theta > 1.0;
+        let rand_f32 = sampler.next_1d();
+        let reflectance_bool = Dielectric::reflectance(cos_theta, refraction_ratio) > rand_f32;
+
+        let direction = if cannot_refract || reflectance_bool {
+            reflect(unit_direction, rec.normal)
+        } else {
+            refract(unit_direction, rec.normal, refraction_ratio)
+        };
+
+        let scattered = Ray::with_time(rec.p, direction, r_in.time);
+        Some(
+            ScatterResults {
+                ray_dir: scattered,
+                norm: direction,
+                attenuation,
+            }
+        )
+    }
+}
+
+// Phase function for participating media: scatters into every direction
+// alike, tinted by `albedo`.
+pub struct Isotropic {
+    pub albedo: Arc<dyn Texture>,
+}
+
+impl Isotropic {
+    pub fn new(albedo: Point) -> Isotropic {
+        Isotropic::textured(Arc::new(SolidColor::new(albedo)))
+    }
+
+    pub fn textured(albedo: Arc<dyn Texture>) -> Isotropic {
+        Isotropic {
+            albedo,
         }
-        let scattered = Ray::new(rec.p, scatter_direction);
-        let attenuation = self.albedo;
-        Some((scattered, attenuation))
     }
 }
 
+impl Material for Isotropic {
+    fn scatter(&self, r_in: Ray, rec: &HitRecord, sampler: &mut dyn Sampler) -> Option<ScatterResults> {
+        let direction = sample_unit_vector(sampler.next_2d());
+        Some(
+            ScatterResults {
+                ray_dir: Ray::with_time(rec.p, direction, r_in.time),
+                norm: direction,
+                attenuation: self.albedo.value(rec.u, rec.v, rec.p),
+            }
+        )
+    }
+}
+
+// Area light: emits `emit` from both sides and absorbs everything that hits it.
+pub struct DiffuseLight {
+    pub emit: Arc<dyn Texture>,
+}
+
+impl DiffuseLight {
+    pub fn new(emit: Point) -> DiffuseLight {
+        DiffuseLight::textured(Arc::new(SolidColor::new(emit)))
+    }
+
+    pub fn textured(emit: Arc<dyn Texture>) -> DiffuseLight {
+        DiffuseLight {
+            emit,
+        }
+    }
+}
+
+impl Material for DiffuseLight {
+    fn scatter(&self, _r_in: Ray, _rec: &HitRecord, _sampler: &mut dyn Sampler) -> Option<ScatterResults> {
+        None
+    }
+
+    fn emitted(&self, rec: &HitRecord) -> Point {
+        self.emit.value(rec.u, rec.v, rec.p)
+    }
+}
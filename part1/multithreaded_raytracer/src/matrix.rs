@@ -0,0 +1,159 @@
+use std::ops::Mul;
+
+use crate::point::Point;
+
+// Row-major 4x4 matrix acting on column vectors, so `a * b` applies `b` first.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Matrix4 {
+    pub m: [[f32; 4]; 4],
+}
+
+impl Matrix4 {
+    pub fn identity() -> Matrix4 {
+        Matrix4::scaling(Point::new(1.0, 1.0, 1.0))
+    }
+
+    pub fn translation(offset: Point) -> Matrix4 {
+        let mut matrix = Matrix4::identity();
+        matrix.m[0][3] = offset.x;
+        matrix.m[1][3] = offset.y;
+        matrix.m[2][3] = offset.z;
+        matrix
+    }
+
+    pub fn scaling(factors: Point) -> Matrix4 {
+        let mut m = [[0.0; 4]; 4];
+        m[0][0] = factors.x;
+        m[1][1] = factors.y;
+        m[2][2] = factors.z;
+        m[3][3] = 1.0;
+        Matrix4 { m }
+    }
+
+    // Right-handed rotation by `degrees` about axis 0 (x), 1 (y) or 2 (z).
+    pub fn rotation(axis: u8, degrees: f32) -> Matrix4 {
+        let (sin, cos) = degrees.to_radians().sin_cos();
+        let (a, b) = match axis {
+            0 => (1, 2),
+            1 => (2, 0),
+            _ => (0, 1),
+        };
+        let mut matrix = Matrix4::identity();
+        matrix.m[a][a] = cos;
+        matrix.m[a][b] = -sin;
+        matrix.m[b][a] = sin;
+        matrix.m[b][b] = cos;
+        matrix
+    }
+
+    pub fn transpose(&self) -> Matrix4 {
+        let mut m = [[0.0; 4]; 4];
+        for (i, row) in m.iter_mut().enumerate() {
+            for (j, value) in row.iter_mut().enumerate() {
+                *value = self.m[j][i];
+            }
+        }
+        Matrix4 { m }
+    }
+
+    // Gauss-Jordan elimination with partial pivoting. None if singular.
+    pub fn inverse(&self) -> Option<Matrix4> {
+        let mut a = self.m;
+        let mut inverse = Matrix4::identity().m;
+        for column in 0..4 {
+            let pivot = (column..4).max_by(|&i, &j| a[i][column].abs().total_cmp(&a[j][column].abs()))?;
+            if a[pivot][column].abs() < 1e-12 {
+                return None;
+            }
+            a.swap(column, pivot);
+            inverse.swap(column, pivot);
+            let scale = 1.0 / a[column][column];
+            for j in 0..4 {
+                a[column][j] *= scale;
+                inverse[column][j] *= scale;
+            }
+            for row in 0..4 {
+                if row != column {
+                    let factor = a[row][column];
+                    for j in 0..4 {
+                        a[row][j] -= factor * a[column][j];
+                        inverse[row][j] -= factor * inverse[column][j];
+                    }
+                }
+            }
+        }
+        Some(Matrix4 { m: inverse })
+    }
+
+    pub fn transform_point(&self, p: Point) -> Point {
+        let m = &self.m;
+        let x = m[0][0] * p.x + m[0][1] * p.y + m[0][2] * p.z + m[0][3];
+        let y = m[1][0] * p.x + m[1][1] * p.y + m[1][2] * p.z + m[1][3];
+        let z = m[2][0] * p.x + m[2][1] * p.y + m[2][2] * p.z + m[2][3];
+        let w = m[3][0] * p.x + m[3][1] * p.y + m[3][2] * p.z + m[3][3];
+        if w == 1.0 { Point::new(x, y, z) } else { Point::new(x / w, y / w, z / w) }
+    }
+
+    // Directions ignore the translation column.
+    pub fn transform_vector(&self, v: Point) -> Point {
+        let m = &self.m;
+        Point::new(m[0][0] * v.x + m[0][1] * v.y + m[0][2] * v.z,
+                   m[1][0] * v.x + m[1][1] * v.y + m[1][2] * v.z,
+                   m[2][0] * v.x + m[2][1] * v.y + m[2][2] * v.z)
+    }
+}
+
+impl Mul<Matrix4> for Matrix4 {
+    type Output = Matrix4;
+
+    fn mul(self, rhs: Matrix4) -> Matrix4 {
+        let mut m = [[0.0; 4]; 4];
+        for (i, row) in m.iter_mut().enumerate() {
+            for (j, value) in row.iter_mut().enumerate() {
+                *value = (0..4).map(|k| self.m[i][k] * rhs.m[k][j]).sum();
+            }
+        }
+        Matrix4 { m }
+    }
+}
+
+#[cfg(test)]
+fn close(a: Point, b: Point) -> bool {
+    (a - b).length() < 1e-5
+}
+
+#[test]
+fn rotations_are_right_handed() {
+    let x = Point::new(1.0, 0.0, 0.0);
+    let y = Point::new(0.0, 1.0, 0.0);
+    let z = Point::new(0.0, 0.0, 1.0);
+    assert!(close(Matrix4::rotation(2, 90.0).transform_vector(x), y));
+    assert!(close(Matrix4::rotation(0, 90.0).transform_vector(y), z));
+    assert!(close(Matrix4::rotation(1, 90.0).transform_vector(z), x));
+}
+
+#[test]
+fn composes_right_to_left() {
+    let m = Matrix4::translation(Point::new(1.0, 2.0, 3.0)) * Matrix4::scaling(Point::new(2.0, 2.0, 2.0));
+    assert_eq!(m.transform_point(Point::new(1.0, 1.0, 1.0)), Point::new(3.0, 4.0, 5.0));
+    assert_eq!(m.transform_vector(Point::new(1.0, 1.0, 1.0)), Point::new(2.0, 2.0, 2.0));
+}
+
+#[test]
+fn inverse_undoes_the_transform() {
+    let m = Matrix4::translation(Point::new(-4.0, 0.5, 7.0))
+        * Matrix4::rotation(1, 33.0)
+        * Matrix4::rotation(0, -71.0)
+        * Matrix4::scaling(Point::new(0.5, 3.0, 1.5));
+    let inverse = m.inverse().unwrap();
+    let p = Point::new(0.3, -2.0, 9.0);
+    assert!(close(inverse.transform_point(m.transform_point(p)), p));
+    let product = m * inverse;
+    for (i, row) in product.m.iter().enumerate() {
+        for (j, value) in row.iter().enumerate() {
+            assert!((value - if i == j { 1.0 } else { 0.0 }).abs() < 1e-5);
+        }
+    }
+    assert!(Matrix4::scaling(Point::new(1.0, 0.0, 1.0)).inverse().is_none());
+    assert_eq!(Matrix4::identity().transpose(), Matrix4::identity());
+}
@@ -0,0 +1,324 @@
+// Triangle meshes and a Wavefront OBJ reader.
+//
+// The reader understands the geometry statements:
+//
+//   v x y z [w]        position
+//   vn x y z           normal
+//   vt u [v [w]]       texture coordinate
+//   f v v v ...        face, each vertex as v, v/vt, v//vn or v/vt/vn
+//
+// Indices start at 1 and negative ones count back from the latest entry.
+// Polygons are split into a fan of triangles. Groups, smoothing groups and
+// material libraries are skipped.
+
+use std::fs;
+use std::io;
+use std::path::Path;
+use std::sync::Arc;
+
+use rand::RngCore;
+
+use crate::{Point, Ray};
+use crate::aabb::AABB;
+use crate::bvh::BVH;
+use crate::hittable::{HitRecord, Hittable};
+use crate::material::Material;
+use crate::triangle::{intersect, triangle_box};
+use crate::utility::{cross, dot, unit_vector};
+
+// Indices into the mesh's vertex arrays.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct MeshTriangle {
+    pub positions: [u32; 3],
+    pub normals: Option<[u32; 3]>,
+    pub uvs: Option<[u32; 3]>,
+}
+
+// Indexed triangle mesh with its own BVH, so the world's tree sees it as one
+// object however many triangles it has.
+pub struct Mesh {
+    pub positions: Vec<Point>,
+    pub normals: Vec<Point>,
+    pub uvs: Vec<(f32, f32)>,
+    pub triangles: Vec<MeshTriangle>,
+    pub material: Arc<dyn Material>,
+    bvh: BVH,
+    bounding_box: Option<AABB>,
+}
+
+impl Mesh {
+    pub fn new(positions: Vec<Point>, normals: Vec<Point>, uvs: Vec<(f32, f32)>, triangles: Vec<MeshTriangle>,
+               material: Arc<dyn Material>) -> Mesh {
+        let boxes: Vec<(usize, AABB)> = triangles.iter()
+            .enumerate()
+            .map(|(i, triangle)| {
+                let [v0, v1, v2] = triangle.positions.map(|p| positions[p as usize]);
+                (i, triangle_box(v0, v1, v2))
+            })
+            .collect();
+        let bounding_box = boxes.iter().map(|&(_, b)| b).reduce(AABB::surrounding_box);
+        Mesh {
+            positions,
+            normals,
+            uvs,
+            triangles,
+            material,
+            bvh: BVH::new(&boxes),
+            bounding_box,
+        }
+    }
+
+    pub fn load_obj(path: &Path, material: Arc<dyn Material>) -> io::Result<Mesh> {
+        Mesh::parse_obj(&fs::read_to_string(path)?, material)
+    }
+
+    pub fn parse_obj(source: &str, material: Arc<dyn Material>) -> io::Result<Mesh> {
+        let mut positions = Vec::new();
+        let mut normals = Vec::new();
+        let mut uvs = Vec::new();
+        let mut triangles = Vec::new();
+
+        for (line_i, line) in source.lines().enumerate() {
+            let invalid = |message: String| {
+                io::Error::new(io::ErrorKind::InvalidData, format!("line {}: {}", line_i + 1, message))
+            };
+            let numbers = |fields: &[&str], min: usize, max: usize| -> io::Result<Vec<f32>> {
+                if fields.len() < min || fields.len() > max {
+                    return Err(invalid(format!("expected {} to {} numbers, found {}", min, max, fields.len())));
+                }
+                fields.iter()
+                    .map(|f| f.parse::<f32>().ok().filter(|n| n.is_finite())
+                        .ok_or_else(|| invalid(format!("expected a number, found '{}'", f))))
+                    .collect()
+            };
+
+            let line = line.split('#').next().unwrap_or("");
+            let fields: Vec<&str> = line.split_whitespace().collect();
+            let Some((&keyword, fields)) = fields.split_first() else { continue };
+            match keyword {
+                "v" => {
+                    let n = numbers(fields, 3, 4)?;
+                    positions.push(Point::new(n[0], n[1], n[2]));
+                }
+                "vn" => {
+                    let n = numbers(fields, 3, 3)?;
+                    normals.push(Point::new(n[0], n[1], n[2]));
+                }
+                "vt" => {
+                    let n = numbers(fields, 1, 3)?;
+                    uvs.push((n[0], n.get(1).copied().unwrap_or(0.0)));
+                }
+                "f" => {
+                    if fields.len() < 3 {
+                        return Err(invalid(format!("a face needs at least three vertices, found {}", fields.len())));
+                    }
+                    let corners = fields.iter()
+                        .map(|corner| parse_corner(corner, positions.len(), uvs.len(), normals.len()).map_err(&invalid))
+                        .collect::<io::Result<Vec<_>>>()?;
+                    let has_uvs = corners.iter().all(|c| c.1.is_some());
+                    let has_normals = corners.iter().all(|c| c.2.is_some());
+                    for i in 1..corners.len() - 1 {
+                        let fan = [corners[0], corners[i], corners[i + 1]];
+                        triangles.push(MeshTriangle {
+                            positions: fan.map(|c| c.0),
+                            uvs: has_uvs.then(|| fan.map(|c| c.1.unwrap_or(0))),
+                            normals: has_normals.then(|| fan.map(|c| c.2.unwrap_or(0))),
+                        });
+                    }
+                }
+                _ => {}
+            }
+        }
+        Ok(Mesh::new(positions, normals, uvs, triangles, material))
+    }
+
+    fn hit_triangle(&self, i: usize, r: Ray, t_min: f32, t_max: f32) -> Option<HitRecord<'_>> {
+        let triangle = &self.triangles[i];
+        let [v0, v1, v2] = triangle.positions.map(|p| self.positions[p as usize]);
+        let (t, b1, b2) = intersect(r, v0, v1, v2, t_min, t_max)?;
+        let geometric_normal = unit_vector(cross(v1 - v0, v2 - v0));
+        // Without texture coordinates the barycentrics are the next best thing.
+        let (u, v) = match triangle.uvs {
+            Some(uvs) => {
+                let [uv0, uv1, uv2] = uvs.map(|i| self.uvs[i as usize]);
+                let b0 = 1.0 - b1 - b2;
+                (b0 * uv0.0 + b1 * uv1.0 + b2 * uv2.0, b0 * uv0.1 + b1 * uv1.1 + b2 * uv2.1)
+            }
+            None => (b1, b2),
+        };
+        let mut rec = HitRecord {
+            p: r.at(t),
+            normal: Point::default(),
+            t,
+            front_face: true,
+            u,
+            v,
+            barycentric: (b1, b2),
+            material: self.material.as_ref(),
+        };
+        // Which side was hit comes from the real surface, the interpolated
+        // normal only changes the shading.
+        rec.set_face_normal(r, geometric_normal);
+        if let Some(normals) = triangle.normals {
+            let [n0, n1, n2] = normals.map(|n| self.normals[n as usize]);
+            let mut shading_normal = unit_vector((1.0 - b1 - b2) * n0 + b1 * n1 + b2 * n2);
+            if dot(shading_normal, geometric_normal) < 0.0 {
+                shading_normal = -shading_normal;
+            }
+            if !shading_normal.x.is_nan() {
+                rec.normal = if rec.front_face { shading_normal } else { -shading_normal };
+            }
+        }
+        Some(rec)
+    }
+}
+
+// "v", "v/vt", "v//vn" or "v/vt/vn", resolved to zero-based indices.
+fn parse_corner(corner: &str, positions: usize, uvs: usize, normals: usize) -> Result<(u32, Option<u32>, Option<u32>), String> {
+    let mut parts = corner.split('/');
+    let position = resolve_index(parts.next().unwrap_or(""), positions, "position")?;
+    let uv = match parts.next() {
+        Some("") | None => None,
+        Some(uv) => Some(resolve_index(uv, uvs, "texture coordinate")?),
+    };
+    let normal = match parts.next() {
+        Some("") | None => None,
+        Some(normal) => Some(resolve_index(normal, normals, "normal")?),
+    };
+    if parts.next().is_some() {
+        return Err(format!("bad face vertex '{}'", corner));
+    }
+    Ok((position, uv, normal))
+}
+
+fn resolve_index(text: &str, count: usize, what: &str) -> Result<u32, String> {
+    let index: i64 = text.parse().map_err(|_| format!("expected a {} index, found '{}'", what, text))?;
+    let resolved = if index < 0 { count as i64 + index } else { index - 1 };
+    if index == 0 || resolved < 0 || resolved >= count as i64 {
+        return Err(format!("{} index {} is out of range, there are {}", what, index, count));
+    }
+    Ok(resolved as u32)
+}
+
+impl Hittable for Mesh {
+    fn hit(&self, r: Ray, t_min: f32, t_max: f32, _rng: &mut dyn RngCore) -> Option<HitRecord<'_>> {
+        self.bvh.hit(r, t_min, t_max, |i, closest_so_far| self.hit_triangle(i, r, t_min, closest_so_far))
+            .map(|(_, rec)| rec)
+    }
+
+    fn get_bounding_box(&self) -> Option<AABB> {
+        self.bounding_box
+    }
+}
+
+#[cfg(test)]
+fn grey() -> Arc<dyn Material> {
+    Arc::new(crate::material::Lambertian::new(Point::new(0.5, 0.5, 0.5)))
+}
+
+#[test]
+fn reads_faces_in_every_form() {
+    let mesh = Mesh::parse_obj("\
+# a unit quad and a triangle
+o thing
+v 0 0 0
+v 1 0 0
+v 1 1 0
+v 0 1 0 1.0
+vt 0 0
+vt 1 0
+vt 1 1
+vn 0 0 1
+s off
+f 1 2 3 4
+f -4/1 -3/2 -2/3
+f 1//1 3//1 4//1
+f 1/1/1 2/2/1 3/3/1
+", grey()).unwrap();
+    assert_eq!(mesh.positions.len(), 4);
+    assert_eq!(mesh.triangles.len(), 5);
+    assert_eq!(mesh.triangles[0], MeshTriangle { positions: [0, 1, 2], normals: None, uvs: None });
+    assert_eq!(mesh.triangles[1], MeshTriangle { positions: [0, 2, 3], normals: None, uvs: None });
+    assert_eq!(mesh.triangles[2], MeshTriangle { positions: [0, 1, 2], normals: None, uvs: Some([0, 1, 2]) });
+    assert_eq!(mesh.triangles[3].normals, Some([0, 0, 0]));
+    assert_eq!(mesh.triangles[4].uvs, Some([0, 1, 2]));
+    let bounding_box = mesh.get_bounding_box().unwrap();
+    assert!(bounding_box.min.x <= 0.0 && bounding_box.max.y >= 1.0);
+}
+
+#[test]
+fn interpolates_texture_coordinates() {
+    let mut rng = crate::random::Pcg32::new(7, 0);
+    let mesh = Mesh::parse_obj("v 0 0 0\nv 1 0 0\nv 0 1 0\nvt 0.5 0.5\nvt 1 0.5\nvt 0.5 1\nf 1/1 2/2 3/3\n", grey()).unwrap();
+    let rec = mesh.hit(Ray::new(Point::new(0.25, 0.5, 1.0), Point::new(0.0, 0.0, -1.0)), 0.001, f32::INFINITY, &mut rng).unwrap();
+    assert!((rec.u - 0.625).abs() < 1e-5 && (rec.v - 0.75).abs() < 1e-5);
+    assert!((rec.barycentric.0 - 0.25).abs() < 1e-5 && (rec.barycentric.1 - 0.5).abs() < 1e-5);
+}
+
+#[test]
+fn reports_bad_lines() {
+    let message = |source: &str| Mesh::parse_obj(source, grey()).err().unwrap().to_string();
+    assert_eq!(message("v 0 0 0\nv 1 0 0\nf 1 2 3"), "line 3: position index 3 is out of range, there are 2");
+    assert!(message("v 0 0\n").starts_with("line 1: expected 3 to 4 numbers"));
+    assert!(message("v 0 0 0\nv 1 0 0\nv 0 1 0\n\nf 1 2").starts_with("line 5:"));
+    assert!(message("v 0 0 0\nf 1 0 1").contains("index 0"));
+    assert!(message("v 0 0 0\nf 1 x 1").contains("'x'"));
+}
+
+#[test]
+fn interpolates_normals_on_the_hit_side() {
+    let mut rng = crate::random::Pcg32::new(7, 0);
+    let mesh = Mesh::parse_obj("\
+v -1 -1 0
+v 1 -1 0
+v 0 1 0
+vn -1 0 1
+vn 1 0 1
+vn 0 0 1
+f 1//1 2//2 3//3
+", grey()).unwrap();
+    let rec = mesh.hit(Ray::new(Point::new(0.0, -0.5, 5.0), Point::new(0.0, 0.0, -1.0)), 0.001, f32::INFINITY, &mut rng).unwrap();
+    assert!(rec.front_face);
+    assert!(rec.normal.x.abs() < 1e-5 && (rec.normal.z - 1.0).abs() < 1e-5);
+
+    let rec = mesh.hit(Ray::new(Point::new(-0.5, -0.9, 5.0), Point::new(0.0, 0.0, -1.0)), 0.001, f32::INFINITY, &mut rng).unwrap();
+    assert!(rec.normal.x < -0.1);
+
+    let rec = mesh.hit(Ray::new(Point::new(-0.5, -0.9, -5.0), Point::new(0.0, 0.0, 1.0)), 0.001, f32::INFINITY, &mut rng).unwrap();
+    assert!(!rec.front_face);
+    assert!(rec.normal.z < 0.0 && rec.normal.x > 0.1);
+}
+
+// A finely tessellated sphere should hit like the real thing.
+#[test]
+fn large_mesh_matches_the_sphere_it_approximates() {
+    use rand::Rng;
+    use std::fmt::Write;
+
+    let (rings, segments) = (150, 300);
+    let mut source = String::new();
+    for i in 0..=rings {
+        let theta = std::f32::consts::PI * i as f32 / rings as f32;
+        for j in 0..segments {
+            let phi = 2.0 * std::f32::consts::PI * j as f32 / segments as f32;
+            writeln!(source, "v {} {} {}", theta.sin() * phi.cos(), theta.cos(), theta.sin() * phi.sin()).unwrap();
+        }
+    }
+    for i in 0..rings {
+        for j in 0..segments {
+            let a = i * segments + j + 1;
+            let b = i * segments + (j + 1) % segments + 1;
+            writeln!(source, "f {} {} {} {}", a, b, b + segments, a + segments).unwrap();
+        }
+    }
+    let mesh = Mesh::parse_obj(&source, grey()).unwrap();
+    assert_eq!(mesh.triangles.len(), 2 * rings * segments);
+
+    let mut rng = crate::random::Pcg32::new(7, 0);
+    for _ in 0..200 {
+        let target = Point::new(rng.gen_range(-0.5..0.5), rng.gen_range(-0.5..0.5), 0.0);
+        let origin = Point::new(0.0, 0.0, 4.0);
+        let rec = mesh.hit(Ray::new(origin, target - origin), 0.001, f32::INFINITY, &mut rng).unwrap();
+        assert!((rec.p.length() - 1.0).abs() < 1e-3);
+    }
+}
@@ -0,0 +1,99 @@
+use std::sync::Arc;
+
+use rand::RngCore;
+
+use crate::{Point, Ray};
+use crate::aabb::AABB;
+use crate::hittable::{HitRecord, Hittable};
+use crate::material::Material;
+use crate::sphere::hit_sphere;
+
+// A sphere whose center slides in a straight line from `center0` at `time0`
+// to `center1` at `time1`, resting at either end outside that interval. Rays
+// see it wherever it is at their own time.
+#[derive(Clone)]
+pub struct MovingSphere {
+    pub center0: Point,
+    pub center1: Point,
+    pub time0: f32,
+    pub time1: f32,
+    pub radius: f32,
+    pub material: Arc<dyn Material>,
+}
+
+impl MovingSphere {
+    pub fn new(center0: Point, center1: Point, time0: f32, time1: f32, radius: f32, material: Arc<dyn Material>) -> MovingSphere {
+        MovingSphere {
+            center0,
+            center1,
+            time0,
+            time1,
+            radius,
+            material,
+        }
+    }
+
+    pub fn center(&self, time: f32) -> Point {
+        if self.time1 == self.time0 {
+            return self.center0;
+        }
+        let along = ((time - self.time0) / (self.time1 - self.time0)).clamp(0.0, 1.0);
+        self.center0 + along * (self.center1 - self.center0)
+    }
+}
+
+impl Hittable for MovingSphere {
+    fn hit(&self, r: Ray, t_min: f32, t_max: f32, _rng: &mut dyn RngCore) -> Option<HitRecord<'_>> {
+        hit_sphere(self.center(r.time), self.radius, self.material.as_ref(), r, t_min, t_max)
+    }
+
+    // Covers the whole path, which is all of it for any shutter.
+    fn get_bounding_box(&self) -> Option<AABB> {
+        let extent = Point::new(self.radius, self.radius, self.radius);
+        Some(AABB::surrounding_box(AABB::new(self.center0 - extent, self.center0 + extent),
+                                   AABB::new(self.center1 - extent, self.center1 + extent)))
+    }
+}
+
+#[cfg(test)]
+#[test]
+fn follows_its_path_over_time() {
+    use crate::material::Lambertian;
+
+    let mut rng = crate::random::Pcg32::new(7, 0);
+    let sphere = MovingSphere::new(Point::new(0.0, 0.0, -5.0), Point::new(4.0, 0.0, -5.0), 0.0, 1.0, 1.0,
+                                   Arc::new(Lambertian::new(Point::new(0.5, 0.5, 0.5))));
+    assert_eq!(sphere.center(0.25), Point::new(1.0, 0.0, -5.0));
+
+    let forward = Point::new(0.0, 0.0, -1.0);
+    let early = Ray::with_time(Point::default(), forward, 0.0);
+    let late = Ray::with_time(Point::default(), forward, 1.0);
+    assert!((sphere.hit(early, 0.001, f32::INFINITY, &mut rng).unwrap().t - 4.0).abs() < 1e-5);
+    assert!(sphere.hit(late, 0.001, f32::INFINITY, &mut rng).is_none());
+    let rec = sphere.hit(Ray::with_time(Point::new(4.0, 0.0, 0.0), forward, 1.0), 0.001, f32::INFINITY, &mut rng).unwrap();
+    assert!((rec.normal - Point::new(0.0, 0.0, 1.0)).length() < 1e-5);
+
+    let bounding_box = sphere.get_bounding_box().unwrap();
+    assert_eq!(bounding_box.min, Point::new(-1.0, -1.0, -6.0));
+    assert_eq!(bounding_box.max, Point::new(5.0, 1.0, -4.0));
+}
+
+#[test]
+fn stays_inside_its_box_for_any_shutter() {
+    use crate::hittable_list::{CheckHits, HittableList};
+    use crate::material::Lambertian;
+    use crate::sphere::Sphere;
+
+    let mut rng = crate::random::Pcg32::new(7, 0);
+    let material: Arc<dyn Material> = Arc::new(Lambertian::new(Point::new(0.5, 0.5, 0.5)));
+    let mut world = HittableList::new();
+    world.add(MovingSphere::new(Point::new(0.0, 0.0, -5.0), Point::new(4.0, 0.0, -5.0), 0.0, 1.0, 1.0, material.clone()));
+    world.add(Sphere::new(Point::new(-20.0, 0.0, -5.0), 1.0, material));
+    let forward = Point::new(0.0, 0.0, -1.0);
+
+    // A shutter of 0..2 finds it parked at center1 for the second half, where
+    // the BVH still looks.
+    assert_eq!(world.get_hits(Ray::with_time(Point::new(4.0, 0.0, 0.0), forward, 2.0), 0.001, f32::INFINITY, &mut rng).unwrap().t, 4.0);
+    assert!(world.get_hits(Ray::with_time(Point::new(8.0, 0.0, 0.0), forward, 2.0), 0.001, f32::INFINITY, &mut rng).is_none());
+    assert!(world.get_hits(Ray::with_time(Point::default(), forward, -1.0), 0.001, f32::INFINITY, &mut rng).is_some());
+}
@@ -0,0 +1,23 @@
+use std::path::Path;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum OutputFormat {
+    Ppm,
+    Png,
+    Hdr,
+}
+
+impl OutputFormat {
+    pub fn from_name(name: &str) -> Option<OutputFormat> {
+        match name.to_ascii_lowercase().as_str() {
+            "ppm" => Some(OutputFormat::Ppm),
+            "png" => Some(OutputFormat::Png),
+            "hdr" => Some(OutputFormat::Hdr),
+            _ => None,
+        }
+    }
+
+    pub fn from_path(path: &Path) -> Option<OutputFormat> {
+        OutputFormat::from_name(path.extension()?.to_str()?)
+    }
+}
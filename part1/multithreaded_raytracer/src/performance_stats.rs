@@ -37,4 +37,10 @@ impl PerformanceStats {
     pub fn time_elapsed(self) -> Duration {
         self.timer.elapsed()
     }
-}
\ No newline at end of file
+}
+
+impl Default for PerformanceStats {
+    fn default() -> Self {
+        PerformanceStats::new()
+    }
+}
@@ -0,0 +1,122 @@
+use rand::Rng;
+
+use crate::point::Point;
+use crate::random::Pcg32;
+use crate::utility::{dot, unit_vector};
+
+const POINT_COUNT: usize = 256;
+
+// Gradient noise on the integer lattice. The tables come from our own PCG32
+// rather than rand's StdRng, whose algorithm may change between releases, so
+// the same seed always builds the same tables and a procedural texture looks
+// the same in every render.
+pub struct Perlin {
+    gradients: Vec<Point>,
+    perm_x: Vec<usize>,
+    perm_y: Vec<usize>,
+    perm_z: Vec<usize>,
+}
+
+impl Perlin {
+    pub fn new(seed: u64) -> Perlin {
+        let mut rng = Pcg32::new(seed, 0);
+        let gradients = (0..POINT_COUNT)
+            .map(|_| loop {
+                let p = Point::new(rng.gen_range(-1.0..1.0), rng.gen_range(-1.0..1.0), rng.gen_range(-1.0..1.0));
+                let length_squared = p.length_squared();
+                if length_squared > 1e-6 && length_squared <= 1.0 {
+                    break unit_vector(p);
+                }
+            })
+            .collect();
+        let mut permutation = || {
+            let mut p: Vec<usize> = (0..POINT_COUNT).collect();
+            for i in (1..POINT_COUNT).rev() {
+                p.swap(i, rng.gen_range(0..=i));
+            }
+            p
+        };
+        Perlin {
+            perm_x: permutation(),
+            perm_y: permutation(),
+            perm_z: permutation(),
+            gradients,
+        }
+    }
+
+    // Roughly -1..1, zero on every lattice point.
+    pub fn noise(&self, p: Point) -> f32 {
+        let (fx, fy, fz) = (p.x.floor(), p.y.floor(), p.z.floor());
+        let (u, v, w) = (p.x - fx, p.y - fy, p.z - fz);
+        let (i, j, k) = (fx as i64, fy as i64, fz as i64);
+        let wrap = |n: i64| n.rem_euclid(POINT_COUNT as i64) as usize;
+
+        // Trilinear blend of the eight corner gradients, eased with a Hermite
+        // cubic so the lattice doesn't show through.
+        let (uu, vv, ww) = (hermite(u), hermite(v), hermite(w));
+        let mut sum = 0.0;
+        for di in 0..2 {
+            for dj in 0..2 {
+                for dk in 0..2 {
+                    let gradient = self.gradients[self.perm_x[wrap(i + di)] ^ self.perm_y[wrap(j + dj)] ^ self.perm_z[wrap(k + dk)]];
+                    let (a, b, c) = (di as f32, dj as f32, dk as f32);
+                    let weight = Point::new(u - a, v - b, w - c);
+                    sum += (a * uu + (1.0 - a) * (1.0 - uu))
+                        * (b * vv + (1.0 - b) * (1.0 - vv))
+                        * (c * ww + (1.0 - c) * (1.0 - ww))
+                        * dot(gradient, weight);
+                }
+            }
+        }
+        sum
+    }
+
+    // Sum of `depth` octaves, each twice the frequency and half the weight of
+    // the one before. Always positive.
+    pub fn turbulence(&self, p: Point, depth: u32) -> f32 {
+        let mut sum = 0.0;
+        let mut p = p;
+        let mut weight = 1.0;
+        for _ in 0..depth {
+            sum += weight * self.noise(p);
+            weight *= 0.5;
+            p = 2.0 * p;
+        }
+        sum.abs()
+    }
+}
+
+fn hermite(t: f32) -> f32 {
+    t * t * (3.0 - 2.0 * t)
+}
+
+#[cfg(test)]
+#[test]
+fn same_seed_same_noise() {
+    let p = Point::new(1.3, -7.1, 0.45);
+    assert_eq!(Perlin::new(3).noise(p), Perlin::new(3).noise(p));
+    assert_ne!(Perlin::new(3).noise(p), Perlin::new(4).noise(p));
+}
+
+#[test]
+fn vanishes_on_the_lattice_and_stays_bounded() {
+    let perlin = Perlin::new(0);
+    assert_eq!(perlin.noise(Point::new(3.0, -2.0, 5.0)), 0.0);
+    let mut seen = (f32::INFINITY, f32::NEG_INFINITY);
+    for i in 0..2000 {
+        let t = i as f32 * 0.0137;
+        let value = perlin.noise(Point::new(t * 3.1, t * 1.7 - 4.0, t * 0.3));
+        assert!(value.abs() <= 1.1, "{}", value);
+        seen = (seen.0.min(value), seen.1.max(value));
+    }
+    assert!(seen.0 < -0.2 && seen.1 > 0.2);
+}
+
+#[test]
+fn is_continuous() {
+    let perlin = Perlin::new(9);
+    let p = Point::new(0.999, 2.5, -0.5);
+    let q = Point::new(1.001, 2.5, -0.5);
+    assert!((perlin.noise(p) - perlin.noise(q)).abs() < 0.01);
+    assert!(perlin.turbulence(p, 7) >= 0.0);
+}
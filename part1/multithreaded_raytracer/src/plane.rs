@@ -0,0 +1,84 @@
+use std::sync::Arc;
+
+use rand::RngCore;
+
+use crate::{Point, Ray};
+use crate::aabb::AABB;
+use crate::hittable::{HitRecord, Hittable};
+use crate::material::Material;
+use crate::utility::{cross, dot, unit_vector};
+
+// Infinite plane through `point`. It has no bounding box, so the world keeps it
+// outside the BVH.
+#[derive(Clone)]
+pub struct Plane {
+    pub point: Point,
+    pub normal: Point,
+    pub material: Arc<dyn Material>,
+}
+
+impl Plane {
+    pub fn new(point: Point, normal: Point, material: Arc<dyn Material>) -> Plane {
+        Plane {
+            point,
+            normal: unit_vector(normal),
+            material,
+        }
+    }
+}
+
+impl Hittable for Plane {
+    fn hit(&self, r: Ray, t_min: f32, t_max: f32, _rng: &mut dyn RngCore) -> Option<HitRecord<'_>> {
+        let denominator = dot(self.normal, r.direction);
+        if denominator.abs() < 1e-8 {
+            return None;
+        }
+        let t = dot(self.point - r.origin, self.normal) / denominator;
+        if !(t_min..=t_max).contains(&t) {
+            return None;
+        }
+        // World-space distances along two directions in the plane, so textures
+        // tile at a fixed size rather than stretching over an infinite surface.
+        let p = r.at(t);
+        let helper = if self.normal.x.abs() > 0.9 { Point::new(0.0, 1.0, 0.0) } else { Point::new(1.0, 0.0, 0.0) };
+        let tangent = unit_vector(cross(helper, self.normal));
+        let bitangent = cross(self.normal, tangent);
+        let mut rec = HitRecord {
+            p,
+            normal: Point::default(),
+            t,
+            front_face: true,
+            u: dot(p - self.point, tangent),
+            v: dot(p - self.point, bitangent),
+            barycentric: (0.0, 0.0),
+            material: self.material.as_ref(),
+        };
+        rec.set_face_normal(r, self.normal);
+        Some(rec)
+    }
+
+    fn get_bounding_box(&self) -> Option<AABB> {
+        None
+    }
+}
+
+#[cfg(test)]
+#[test]
+fn hits_from_either_side() {
+    let mut rng = crate::random::Pcg32::new(7, 0);
+    let grey = Arc::new(crate::material::Lambertian::new(Point::new(0.5, 0.5, 0.5)));
+    let floor = Plane::new(Point::new(0.0, -1.0, 0.0), Point::new(0.0, 2.0, 0.0), grey);
+
+    let rec = floor.hit(Ray::new(Point::new(50.0, 1.0, -7.0), Point::new(0.0, -1.0, 0.0)), 0.001, f32::INFINITY, &mut rng).unwrap();
+    assert!((rec.t - 2.0).abs() < 1e-5);
+    assert!(rec.front_face);
+    assert_eq!(rec.normal, Point::new(0.0, 1.0, 0.0));
+
+    let rec = floor.hit(Ray::new(Point::new(0.0, -3.0, 0.0), Point::new(1.0, 1.0, 0.0)), 0.001, f32::INFINITY, &mut rng).unwrap();
+    assert!(!rec.front_face);
+    assert_eq!(rec.normal, Point::new(0.0, -1.0, 0.0));
+
+    assert!(floor.hit(Ray::new(Point::default(), Point::new(1.0, 0.0, 0.0)), 0.001, f32::INFINITY, &mut rng).is_none());
+    assert!(floor.hit(Ray::new(Point::default(), Point::new(0.0, 1.0, 0.0)), 0.001, f32::INFINITY, &mut rng).is_none());
+    assert!(floor.get_bounding_box().is_none());
+}
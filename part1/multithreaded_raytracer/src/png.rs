@@ -0,0 +1,419 @@
+use std::fs;
+use std::io;
+use std::path::Path;
+
+use crate::image::Image;
+use crate::zlib;
+
+const SIGNATURE: [u8; 8] = [0x89, b'P', b'N', b'G', b'\r', b'\n', 0x1a, b'\n'];
+
+const CRC_TABLE: [u32; 256] = crc_table();
+
+const fn crc_table() -> [u32; 256] {
+    let mut table = [0u32; 256];
+    let mut n = 0;
+    while n < 256 {
+        let mut c = n as u32;
+        let mut k = 0;
+        while k < 8 {
+            c = if c & 1 != 0 { 0xedb88320 ^ (c >> 1) } else { c >> 1 };
+            k += 1;
+        }
+        table[n] = c;
+        n += 1;
+    }
+    table
+}
+
+pub fn crc32(bytes: &[u8]) -> u32 {
+    !bytes.iter().fold(!0u32, |c, &b| CRC_TABLE[((c ^ b as u32) & 0xff) as usize] ^ (c >> 8))
+}
+
+fn paeth(a: u8, b: u8, c: u8) -> u8 {
+    let p = a as i16 + b as i16 - c as i16;
+    let pa = (p - a as i16).abs();
+    let pb = (p - b as i16).abs();
+    let pc = (p - c as i16).abs();
+    if pa <= pb && pa <= pc {
+        a
+    } else if pb <= pc {
+        b
+    } else {
+        c
+    }
+}
+
+// The IHDR fields decoding cares about.
+struct Header {
+    width: u32,
+    height: u32,
+    depth: u8,
+    color_type: u8,
+}
+
+impl Header {
+    fn parse(data: &[u8]) -> io::Result<Header> {
+        let header = Header {
+            width: u32::from_be_bytes([data[0], data[1], data[2], data[3]]),
+            height: u32::from_be_bytes([data[4], data[5], data[6], data[7]]),
+            depth: data[8],
+            color_type: data[9],
+        };
+        let depths: &[u8] = match header.color_type {
+            0 => &[1, 2, 4, 8, 16],
+            3 => &[1, 2, 4, 8],
+            2 | 4 | 6 => &[8, 16],
+            _ => return Err(invalid(&format!("unknown color type {}", header.color_type))),
+        };
+        if !depths.contains(&header.depth) {
+            return Err(invalid(&format!("bit depth {} isn't allowed with color type {}", header.depth, header.color_type)));
+        }
+        if data[10..12] != [0, 0] {
+            return Err(invalid("unknown compression or filter method"));
+        }
+        if data[12] != 0 {
+            return Err(invalid("interlaced images are not supported"));
+        }
+        Ok(header)
+    }
+
+    fn channels(&self) -> usize {
+        match self.color_type {
+            2 => 3,
+            4 => 2,
+            6 => 4,
+            _ => 1,
+        }
+    }
+
+    // Scales a gray or color sample to 0..=255.
+    fn to_8bit(&self, value: u16) -> u8 {
+        match self.depth {
+            16 => (value >> 8) as u8,
+            8 => value as u8,
+            depth => (value as u32 * 255 / ((1 << depth) - 1)) as u8,
+        }
+    }
+}
+
+// Sample number `index` of an unfiltered row, samples packed big-endian at
+// `depth` bits each.
+fn sample(row: &[u8], index: usize, depth: u8) -> u16 {
+    match depth {
+        16 => u16::from_be_bytes([row[2 * index], row[2 * index + 1]]),
+        8 => row[index] as u16,
+        depth => {
+            let bit = index * depth as usize;
+            let shift = 8 - depth as usize - bit % 8;
+            ((row[bit / 8] >> shift) & ((1 << depth) - 1)) as u16
+        }
+    }
+}
+
+// Applies filter `kind` to `row` given the unfiltered row above it.
+fn filter_row(kind: u8, row: &[u8], above: &[u8], bpp: usize, out: &mut Vec<u8>) {
+    out.push(kind);
+    for i in 0..row.len() {
+        let a = if i >= bpp { row[i - bpp] } else { 0 };
+        let b = above[i];
+        let c = if i >= bpp { above[i - bpp] } else { 0 };
+        let predicted = match kind {
+            0 => 0,
+            1 => a,
+            2 => b,
+            3 => ((a as u16 + b as u16) / 2) as u8,
+            _ => paeth(a, b, c),
+        };
+        out.push(row[i].wrapping_sub(predicted));
+    }
+}
+
+// Undoes the filter on `row` in place given the already reconstructed row
+// above, empty for the first row.
+fn unfilter_row(kind: u8, row: &mut [u8], above: &[u8], bpp: usize) -> io::Result<()> {
+    for i in 0..row.len() {
+        let a = if i >= bpp { row[i - bpp] } else { 0 };
+        let b = above.get(i).copied().unwrap_or(0);
+        let c = if i >= bpp { above.get(i - bpp).copied().unwrap_or(0) } else { 0 };
+        let predicted = match kind {
+            0 => 0,
+            1 => a,
+            2 => b,
+            3 => ((a as u16 + b as u16) / 2) as u8,
+            4 => paeth(a, b, c),
+            _ => return Err(invalid(&format!("unknown filter type {}", kind))),
+        };
+        row[i] = row[i].wrapping_add(predicted);
+    }
+    Ok(())
+}
+
+fn invalid(message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, format!("bad PNG: {}", message))
+}
+
+fn write_chunk(out: &mut Vec<u8>, kind: &[u8; 4], data: &[u8]) {
+    out.extend_from_slice(&(data.len() as u32).to_be_bytes());
+    let start = out.len();
+    out.extend_from_slice(kind);
+    out.extend_from_slice(data);
+    let crc = crc32(&out[start..]);
+    out.extend_from_slice(&crc.to_be_bytes());
+}
+
+#[derive(Clone)]
+pub struct PNG {
+    pub image: Image,
+}
+
+impl PNG {
+    pub fn new(image: Image) -> PNG {
+        PNG {
+            image,
+        }
+    }
+
+    // 8-bit RGB, non-interlaced. Each row uses whichever filter leaves the
+    // smallest sum of absolute differences, the usual heuristic.
+    pub fn encode(&self) -> Vec<u8> {
+        let stride = self.image.width as usize * 3;
+        let mut filtered = Vec::with_capacity((stride + 1) * self.image.height as usize);
+        let mut candidate = Vec::with_capacity(stride + 1);
+        let mut best = Vec::with_capacity(stride + 1);
+        let zero_row = vec![0; stride];
+        for (y, row) in self.image.data.chunks(stride.max(1)).enumerate().take(self.image.height as usize) {
+            let above = if y == 0 { &zero_row[..] } else { &self.image.data[(y - 1) * stride..y * stride] };
+            let mut best_score = u64::MAX;
+            for kind in 0..5 {
+                candidate.clear();
+                filter_row(kind, row, above, 3, &mut candidate);
+                let score = candidate[1..].iter().map(|&v| (v as i8).unsigned_abs() as u64).sum();
+                if score < best_score {
+                    best_score = score;
+                    std::mem::swap(&mut best, &mut candidate);
+                }
+            }
+            filtered.extend_from_slice(&best);
+        }
+
+        let mut header = Vec::with_capacity(13);
+        header.extend_from_slice(&self.image.width.to_be_bytes());
+        header.extend_from_slice(&self.image.height.to_be_bytes());
+        header.extend_from_slice(&[8, 2, 0, 0, 0]); // bit depth, RGB, deflate, filter method, no interlace
+
+        let mut out = SIGNATURE.to_vec();
+        write_chunk(&mut out, b"IHDR", &header);
+        write_chunk(&mut out, b"IDAT", &zlib::compress(&filtered));
+        write_chunk(&mut out, b"IEND", &[]);
+        out
+    }
+
+    pub fn write_file(&self, path: &Path) -> io::Result<()> {
+        fs::write(path, self.encode())
+    }
+
+    // Reads non-interlaced grayscale, RGB and palette images, with or without
+    // alpha, at any bit depth the format allows. Gray and palette colors are
+    // expanded to RGB, 16-bit samples cut to 8 and alpha dropped.
+    pub fn decode(bytes: &[u8]) -> io::Result<PNG> {
+        if !bytes.starts_with(&SIGNATURE) {
+            return Err(invalid("missing signature"));
+        }
+        let mut position = SIGNATURE.len();
+        let mut header: Option<Header> = None;
+        let mut palette: Vec<[u8; 3]> = Vec::new();
+        let mut compressed = Vec::new();
+        loop {
+            let length_bytes = bytes.get(position..position + 4).ok_or_else(|| invalid("truncated chunk"))?;
+            let length = u32::from_be_bytes([length_bytes[0], length_bytes[1], length_bytes[2], length_bytes[3]]) as usize;
+            let body = bytes.get(position + 4..position + 8 + length).ok_or_else(|| invalid("truncated chunk"))?;
+            let crc_bytes = bytes.get(position + 8 + length..position + 12 + length).ok_or_else(|| invalid("truncated chunk"))?;
+            if crc32(body) != u32::from_be_bytes([crc_bytes[0], crc_bytes[1], crc_bytes[2], crc_bytes[3]]) {
+                return Err(invalid("chunk checksum mismatch"));
+            }
+            position += 12 + length;
+            let (kind, data) = body.split_at(4);
+            match kind {
+                b"IHDR" => {
+                    if data.len() != 13 {
+                        return Err(invalid("bad header length"));
+                    }
+                    header = Some(Header::parse(data)?);
+                }
+                b"PLTE" => {
+                    if data.is_empty() || data.len() % 3 != 0 || data.len() > 256 * 3 {
+                        return Err(invalid("bad palette length"));
+                    }
+                    palette = data.chunks(3).map(|rgb| [rgb[0], rgb[1], rgb[2]]).collect();
+                }
+                b"IDAT" => compressed.extend_from_slice(data),
+                b"IEND" => break,
+                _ if kind[0] & 0x20 == 0 => {
+                    return Err(invalid(&format!("unknown critical chunk {}", String::from_utf8_lossy(kind))));
+                }
+                _ => {}
+            }
+        }
+
+        let header = header.ok_or_else(|| invalid("missing header"))?;
+        if header.color_type == 3 && palette.is_empty() {
+            return Err(invalid("palette image without a palette"));
+        }
+        let (width, height) = (header.width as usize, header.height as usize);
+        let bits_per_pixel = header.channels() * header.depth as usize;
+        let too_big = || invalid("image is too large");
+        let stride = width.checked_mul(bits_per_pixel).ok_or_else(too_big)?.div_ceil(8);
+        let expected = (stride + 1).checked_mul(height).ok_or_else(too_big)?;
+        let filtered = zlib::decompress(&compressed, expected)?;
+        if filtered.len() != expected {
+            return Err(invalid("image data has the wrong size"));
+        }
+
+        // Filters work on whole bytes, a pixel apart or one byte for depths
+        // under 8.
+        let bpp = (bits_per_pixel / 8).max(1);
+        let mut raw = vec![0; stride * height];
+        for y in 0..height {
+            let line = &filtered[y * (stride + 1)..(y + 1) * (stride + 1)];
+            let (above, row) = raw.split_at_mut(y * stride);
+            let row = &mut row[..stride];
+            row.copy_from_slice(&line[1..]);
+            let above = if y == 0 { &[][..] } else { &above[(y - 1) * stride..] };
+            unfilter_row(line[0], row, above, bpp)?;
+        }
+
+        let mut image = Image::new(header.width, header.height);
+        let channels = header.channels();
+        for (y, row) in raw.chunks(stride.max(1)).enumerate().take(height) {
+            for x in 0..width {
+                let rgb = match header.color_type {
+                    3 => {
+                        let index = sample(row, x, header.depth) as usize;
+                        *palette.get(index).ok_or_else(|| invalid("palette index out of range"))?
+                    }
+                    0 | 4 => {
+                        let gray = header.to_8bit(sample(row, x * channels, header.depth));
+                        [gray; 3]
+                    }
+                    _ => [0, 1, 2].map(|c| header.to_8bit(sample(row, x * channels + c, header.depth))),
+                };
+                let i = (y * width + x) * 3;
+                image.data[i..i + 3].copy_from_slice(&rgb);
+            }
+        }
+        Ok(PNG { image })
+    }
+
+    pub fn read_file(path: &Path) -> io::Result<PNG> {
+        PNG::decode(&fs::read(path)?)
+    }
+}
+
+#[cfg(test)]
+#[test]
+fn crc_of_iend() {
+    assert_eq!(crc32(b"IEND"), 0xae426082);
+}
+
+#[test]
+fn round_trips_a_small_image() {
+    let mut image = Image::new(7, 5);
+    for (i, value) in image.data.iter_mut().enumerate() {
+        *value = ((i * 37) ^ (i / 3 * 11)) as u8;
+    }
+    let encoded = PNG::new(image.clone()).encode();
+    assert!(encoded.starts_with(&SIGNATURE));
+    assert_eq!(&encoded[12..16], b"IHDR");
+    assert_eq!(PNG::decode(&encoded).unwrap().image, image);
+}
+
+#[test]
+fn round_trips_a_gradient() {
+    let mut image = Image::new(64, 48);
+    for y in 0..48 {
+        for x in 0..64 {
+            let i = (y * 64 + x) * 3;
+            image.data[i] = (x * 4) as u8;
+            image.data[i + 1] = (y * 5) as u8;
+            image.data[i + 2] = 128;
+        }
+    }
+    let encoded = PNG::new(image.clone()).encode();
+    assert!(encoded.len() < image.data.len() / 4);
+    assert_eq!(PNG::decode(&encoded).unwrap().image, image);
+}
+
+#[test]
+fn rejects_a_corrupt_chunk() {
+    let mut encoded = PNG::new(Image::new(3, 3)).encode();
+    encoded[20] ^= 0xff;
+    assert!(PNG::decode(&encoded).is_err());
+}
+
+// Builds a PNG of any color type from unfiltered rows, filtered with Paeth so
+// decoding has some work to do.
+#[cfg(test)]
+fn encode_raw(width: u32, height: u32, depth: u8, color_type: u8, palette: &[u8], rows: &[&[u8]]) -> Vec<u8> {
+    let mut filtered = Vec::new();
+    let mut above = vec![0; rows[0].len()];
+    let bpp = ((depth as usize * [1, 0, 3, 1, 2, 0, 4][color_type as usize]) / 8).max(1);
+    for row in rows {
+        filter_row(4, row, &above, bpp, &mut filtered);
+        above = row.to_vec();
+    }
+    let mut header = Vec::new();
+    header.extend_from_slice(&width.to_be_bytes());
+    header.extend_from_slice(&height.to_be_bytes());
+    header.extend_from_slice(&[depth, color_type, 0, 0, 0]);
+    let mut out = SIGNATURE.to_vec();
+    write_chunk(&mut out, b"IHDR", &header);
+    if !palette.is_empty() {
+        write_chunk(&mut out, b"PLTE", palette);
+    }
+    write_chunk(&mut out, b"IDAT", &zlib::compress(&filtered));
+    write_chunk(&mut out, b"IEND", &[]);
+    out
+}
+
+#[test]
+fn reads_grayscale() {
+    let png = encode_raw(2, 2, 8, 0, &[], &[&[0, 100], &[200, 255]]);
+    assert_eq!(PNG::decode(&png).unwrap().image.data, [0, 0, 0, 100, 100, 100, 200, 200, 200, 255, 255, 255]);
+    // Two bits a pixel, scaled so 3 is white.
+    let png = encode_raw(5, 1, 2, 0, &[], &[&[0b00_01_10_11, 0b11_000000]]);
+    assert_eq!(PNG::decode(&png).unwrap().image.data, [0, 0, 0, 85, 85, 85, 170, 170, 170, 255, 255, 255, 255, 255, 255]);
+}
+
+#[test]
+fn reads_sixteen_bit_rgb() {
+    let png = encode_raw(1, 2, 16, 2, &[], &[&[0x12, 0x34, 0xab, 0xcd, 0xff, 0xff], &[0, 0, 1, 0, 0x80, 0]]);
+    assert_eq!(PNG::decode(&png).unwrap().image.data, [0x12, 0xab, 0xff, 0, 1, 0x80]);
+}
+
+#[test]
+fn reads_palette_images() {
+    let palette = [255, 0, 0, 0, 255, 0, 0, 0, 255];
+    let png = encode_raw(3, 1, 8, 3, &palette, &[&[2, 0, 1]]);
+    assert_eq!(PNG::decode(&png).unwrap().image.data, [0, 0, 255, 255, 0, 0, 0, 255, 0]);
+    let png = encode_raw(3, 1, 4, 3, &palette, &[&[0x21, 0x00]]);
+    assert_eq!(PNG::decode(&png).unwrap().image.data, [0, 0, 255, 0, 255, 0, 255, 0, 0]);
+    assert!(PNG::decode(&encode_raw(1, 1, 8, 3, &palette, &[&[3]])).is_err());
+    assert!(PNG::decode(&encode_raw(1, 1, 8, 3, &[], &[&[0]])).is_err());
+}
+
+#[test]
+fn drops_alpha() {
+    let png = encode_raw(2, 1, 8, 4, &[], &[&[40, 255, 90, 0]]);
+    assert_eq!(PNG::decode(&png).unwrap().image.data, [40, 40, 40, 90, 90, 90]);
+    let png = encode_raw(2, 1, 8, 6, &[], &[&[1, 2, 3, 255, 4, 5, 6, 0]]);
+    assert_eq!(PNG::decode(&png).unwrap().image.data, [1, 2, 3, 4, 5, 6]);
+    let png = encode_raw(1, 1, 16, 6, &[], &[&[1, 0, 2, 0, 3, 0, 0, 0]]);
+    assert_eq!(PNG::decode(&png).unwrap().image.data, [1, 2, 3]);
+}
+
+#[test]
+fn rejects_unsupported_headers() {
+    assert!(PNG::decode(&encode_raw(1, 1, 16, 3, &[0, 0, 0], &[&[0, 0]])).is_err());
+    assert!(PNG::decode(&encode_raw(1, 1, 8, 5, &[], &[&[0]])).is_err());
+    assert!(PNG::decode(&encode_raw(1, 1, 4, 2, &[], &[&[0, 0]])).is_err());
+}
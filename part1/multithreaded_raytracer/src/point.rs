@@ -2,7 +2,7 @@ use std::ops::{Add, Div, Index, IndexMut, Mul, Neg, Sub};
 
 pub const K_EPSILON: f32 = 0.00000001;
 
-#[derive(Clone, Copy, PartialEq, Debug)]
+#[derive(Clone, Copy, PartialEq, Debug, Default)]
 pub struct Point {
     pub x: f32,
     pub y: f32,
@@ -17,14 +17,6 @@ impl Point {
             z,
         }
     }
-    pub fn default() -> Self {
-        Point {
-            x: 0.0,
-            y: 0.0,
-            z: 0.0,
-        }
-    }
-
     pub fn length_squared(&self) -> f32 {
         (self.x * self.x) + (self.y * self.y) + (self.z * self.z)
     }
@@ -56,7 +48,7 @@ impl Neg for Point {
     type Output = Point;
 
     fn neg(self) -> Self::Output {
-        Point::new(-1.0 * self.x, -1.0 * self.y, -1.0 * self.z)
+        Point::new(-self.x, -self.y, -self.z)
     }
 }
 
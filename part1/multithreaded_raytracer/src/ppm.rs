@@ -1,21 +1,156 @@
-use std::fs::File;
-use std::io::Write;
+use std::fs;
+use std::io;
 use std::path::Path;
 
+use crate::image::Image;
+
+fn invalid(message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, format!("bad PPM: {}", message))
+}
+
+// Walks the whitespace separated header fields, skipping '#' comments.
+struct Header<'a> {
+    bytes: &'a [u8],
+    position: usize,
+}
+
+impl Header<'_> {
+    fn skip_space(&mut self) {
+        while let Some(&b) = self.bytes.get(self.position) {
+            if b == b'#' {
+                while self.bytes.get(self.position).is_some_and(|&b| b != b'\n') {
+                    self.position += 1;
+                }
+            } else if b.is_ascii_whitespace() {
+                self.position += 1;
+            } else {
+                break;
+            }
+        }
+    }
+
+    fn number(&mut self, what: &str) -> io::Result<u32> {
+        self.skip_space();
+        let start = self.position;
+        while self.bytes.get(self.position).is_some_and(|b| b.is_ascii_digit()) {
+            self.position += 1;
+        }
+        std::str::from_utf8(&self.bytes[start..self.position]).ok()
+            .and_then(|digits| digits.parse().ok())
+            .ok_or_else(|| invalid(&format!("expected the {}", what)))
+    }
+}
+
 #[derive(Clone)]
 pub struct PPM {
-    pub(crate) height: u32,
-    pub(crate) width: u32,
-    pub(crate) data: Vec<u8>,
+    pub image: Image,
 }
 
 impl PPM {
-    pub fn write_file(&self, filename: &str) -> std::io::Result<()> {
-        let path = Path::new(filename);
-        let mut file = File::create(&path)?;
-        let header = format!("P6 {} {} 255\n", self.width, self.height);
-        file.write(header.as_bytes())?;
-        file.write(&self.data)?;
-        Ok(())
-    }
-}
\ No newline at end of file
+    pub fn new(image: Image) -> PPM {
+        PPM {
+            image,
+        }
+    }
+
+    pub fn encode(&self) -> Vec<u8> {
+        let mut out = format!("P6 {} {} 255\n", self.image.width, self.image.height).into_bytes();
+        out.extend_from_slice(&self.image.data);
+        out
+    }
+
+    pub fn write_file(&self, path: &Path) -> io::Result<()> {
+        fs::write(path, self.encode())
+    }
+
+    // Reads binary (P6) and ASCII (P3) files with any maximum value up to
+    // 65535, rescaled to 8 bits.
+    pub fn decode(bytes: &[u8]) -> io::Result<PPM> {
+        let binary = match bytes.get(..2) {
+            Some(b"P6") => true,
+            Some(b"P3") => false,
+            _ => return Err(invalid("expected P3 or P6")),
+        };
+        let mut header = Header { bytes, position: 2 };
+        let width = header.number("width")?;
+        let height = header.number("height")?;
+        let max = header.number("maximum value")?;
+        if max == 0 || max > 65535 {
+            return Err(invalid("maximum value must be between 1 and 65535"));
+        }
+        // Check the file can hold every sample before allocating for them, so
+        // a bogus header can't ask for gigabytes. ASCII samples take at least
+        // a byte each.
+        let size = if binary && max > 255 { 2 } else { 1 };
+        let start = header.position + 1;
+        let needed = (width as usize).checked_mul(height as usize).and_then(|pixels| pixels.checked_mul(3 * size));
+        let needed = match needed {
+            Some(needed) if needed <= bytes.len().saturating_sub(start) => needed,
+            _ => return Err(invalid("truncated image data")),
+        };
+        let mut image = Image::new(width, height);
+        let scale = |value: u32| -> io::Result<u8> {
+            if value > max {
+                return Err(invalid("sample above the maximum value"));
+            }
+            Ok(((value * 255 + max / 2) / max) as u8)
+        };
+
+        if binary {
+            // Exactly one whitespace byte separates the header from the samples.
+            let samples = &bytes[start..start + needed];
+            for (value, sample) in image.data.iter_mut().zip(samples.chunks(size)) {
+                *value = scale(sample.iter().fold(0, |v, &b| v << 8 | b as u32))?;
+            }
+        } else {
+            for value in image.data.iter_mut() {
+                *value = scale(header.number("next sample")?)?;
+            }
+        }
+        Ok(PPM { image })
+    }
+
+    pub fn read_file(path: &Path) -> io::Result<PPM> {
+        PPM::decode(&fs::read(path)?)
+    }
+}
+
+#[cfg(test)]
+#[test]
+fn round_trips_binary() {
+    let mut image = Image::new(5, 3);
+    for (i, value) in image.data.iter_mut().enumerate() {
+        *value = (i * 17) as u8;
+    }
+    assert_eq!(PPM::decode(&PPM::new(image.clone()).encode()).unwrap().image, image);
+}
+
+#[test]
+fn reads_ascii_with_comments() {
+    let source = b"P3\n# made by hand\n2 1 # width and height\n15\n15 0 0   0 15 7\n";
+    let image = PPM::decode(source).unwrap().image;
+    assert_eq!((image.width, image.height), (2, 1));
+    assert_eq!(image.data, vec![255, 0, 0, 0, 255, 119]);
+}
+
+#[test]
+fn reads_sixteen_bit_samples() {
+    let mut source = b"P6 1 1 65535\n".to_vec();
+    source.extend_from_slice(&[0xff, 0xff, 0x80, 0x00, 0x00, 0x00]);
+    assert_eq!(PPM::decode(&source).unwrap().image.data, vec![255, 128, 0]);
+}
+
+#[test]
+fn rejects_bad_files() {
+    assert!(PPM::decode(b"P5 1 1 255\n\0").is_err());
+    assert!(PPM::decode(b"P6 2 2 255\n\0\0\0").is_err());
+    assert!(PPM::decode(b"P3 1 1 255\n0 300 0").is_err());
+    assert!(PPM::decode(b"P3 1 1 255\n0 0").is_err());
+}
+
+#[test]
+fn rejects_sizes_larger_than_the_file() {
+    assert!(PPM::decode(b"P6 65535 65535 255\n\0\0\0").is_err());
+    assert!(PPM::decode(b"P3 65535 65535 255\n0 0 0").is_err());
+    assert!(PPM::decode(b"P6 4294967295 4294967295 65535\n\0\0\0").is_err());
+}
@@ -0,0 +1,94 @@
+use rand::RngCore;
+
+// PCG32 (XSH-RR) from O'Neill's paper: small, fast and fully determined by
+// its seed and stream, so every pixel can own an independent generator and a
+// render comes out the same however its tiles are spread over threads.
+#[derive(Clone, Debug)]
+pub struct Pcg32 {
+    state: u64,
+    increment: u64,
+}
+
+const MULTIPLIER: u64 = 6364136223846793005;
+
+impl Pcg32 {
+    // Generators with different streams never share a sequence, even with
+    // the same seed.
+    pub fn new(seed: u64, stream: u64) -> Pcg32 {
+        let mut rng = Pcg32 {
+            state: 0,
+            increment: (stream << 1) | 1,
+        };
+        rng.step();
+        rng.state = rng.state.wrapping_add(seed);
+        rng.step();
+        rng
+    }
+
+    // The generator for one sample of one pixel of a render seeded with `seed`.
+    pub fn for_sample(seed: u64, x: u32, y: u32, sample: u32) -> Pcg32 {
+        Pcg32::new(mix(seed ^ mix(sample as u64)), (y as u64) << 32 | x as u64)
+    }
+
+    fn step(&mut self) {
+        self.state = self.state.wrapping_mul(MULTIPLIER).wrapping_add(self.increment);
+    }
+}
+
+impl RngCore for Pcg32 {
+    fn next_u32(&mut self) -> u32 {
+        let old = self.state;
+        self.step();
+        let xorshifted = (((old >> 18) ^ old) >> 27) as u32;
+        xorshifted.rotate_right((old >> 59) as u32)
+    }
+
+    fn next_u64(&mut self) -> u64 {
+        (self.next_u32() as u64) << 32 | self.next_u32() as u64
+    }
+
+    fn fill_bytes(&mut self, dest: &mut [u8]) {
+        for chunk in dest.chunks_mut(4) {
+            let bytes = self.next_u32().to_le_bytes();
+            chunk.copy_from_slice(&bytes[..chunk.len()]);
+        }
+    }
+
+    fn try_fill_bytes(&mut self, dest: &mut [u8]) -> Result<(), rand::Error> {
+        self.fill_bytes(dest);
+        Ok(())
+    }
+}
+
+// SplitMix64's finalizer, so nearby seeds start far apart.
+pub fn mix(mut z: u64) -> u64 {
+    z = z.wrapping_add(0x9e3779b97f4a7c15);
+    z = (z ^ (z >> 30)).wrapping_mul(0xbf58476d1ce4e5b9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94d049bb133111eb);
+    z ^ (z >> 31)
+}
+
+#[cfg(test)]
+#[test]
+fn matches_the_reference_output() {
+    // First outputs of pcg32-demo, which seeds with 42 on stream 54.
+    let mut rng = Pcg32::new(42, 54);
+    let expected = [0xa15c02b7, 0x7b47f409, 0xba1d3330, 0x83d2f293, 0xbfa4784b, 0xcbed606e];
+    for value in expected {
+        assert_eq!(rng.next_u32(), value);
+    }
+}
+
+#[test]
+fn pixels_get_their_own_sequences() {
+    use rand::Rng;
+
+    let draw = |mut rng: Pcg32| (0..8).map(|_| rng.gen::<f32>()).collect::<Vec<_>>();
+    assert_eq!(draw(Pcg32::for_sample(7, 3, 4, 0)), draw(Pcg32::for_sample(7, 3, 4, 0)));
+    assert_ne!(draw(Pcg32::for_sample(7, 3, 4, 0)), draw(Pcg32::for_sample(7, 4, 3, 0)));
+    assert_ne!(draw(Pcg32::for_sample(7, 3, 4, 0)), draw(Pcg32::for_sample(8, 3, 4, 0)));
+    assert_ne!(draw(Pcg32::for_sample(7, 3, 4, 0)), draw(Pcg32::for_sample(7, 3, 4, 1)));
+    let mut bytes = [0u8; 7];
+    Pcg32::new(1, 1).fill_bytes(&mut bytes);
+    assert!(bytes.iter().any(|&b| b != 0));
+}
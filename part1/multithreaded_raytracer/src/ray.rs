@@ -2,15 +2,22 @@ use crate::Point;
 
 #[derive(Clone, Copy)]
 pub struct Ray {
-    pub(crate) origin: Point,
-    pub(crate) direction: Point,
+    pub origin: Point,
+    pub direction: Point,
+    // When during the shutter interval the ray was sent, for motion blur.
+    pub time: f32,
 }
 
 impl Ray {
     pub fn new(origin: Point, direction: Point) -> Self {
+        Ray::with_time(origin, direction, 0.0)
+    }
+
+    pub fn with_time(origin: Point, direction: Point, time: f32) -> Self {
         Ray {
             origin,
             direction,
+            time,
         }
     }
 
@@ -0,0 +1,169 @@
+use std::sync::Arc;
+
+use rand::RngCore;
+
+use crate::{Point, Ray};
+use crate::aabb::AABB;
+use crate::hittable::{HitRecord, Hittable};
+use crate::material::Material;
+
+// Rectangles are infinitely thin, so their boxes get this much thickness
+// to keep the slab test from missing them.
+const PAD: f32 = 0.0001;
+
+// Axis-aligned rectangle spanning [a0, a1] x [b0, b1] on the plane where axis
+// `k` equals `k`. `a` and `b` are the other two axes in order.
+#[derive(Clone)]
+struct AxisRect {
+    axes: (u8, u8, u8),
+    a0: f32,
+    a1: f32,
+    b0: f32,
+    b1: f32,
+    k: f32,
+    material: Arc<dyn Material>,
+}
+
+impl AxisRect {
+    fn hit(&self, r: Ray, t_min: f32, t_max: f32) -> Option<HitRecord<'_>> {
+        let (a, b, k) = self.axes;
+        let t = (self.k - r.origin[k]) / r.direction[k];
+        if !(t_min..=t_max).contains(&t) {
+            return None;
+        }
+        let p = r.at(t);
+        if p[a] < self.a0 || p[a] > self.a1 || p[b] < self.b0 || p[b] > self.b1 {
+            return None;
+        }
+        let mut outward_normal = Point::default();
+        outward_normal[k] = 1.0;
+        let mut rec = HitRecord {
+            p,
+            normal: Point::default(),
+            t,
+            front_face: true,
+            u: (p[a] - self.a0) / (self.a1 - self.a0),
+            v: (p[b] - self.b0) / (self.b1 - self.b0),
+            barycentric: (0.0, 0.0),
+            material: self.material.as_ref(),
+        };
+        rec.set_face_normal(r, outward_normal);
+        Some(rec)
+    }
+
+    fn bounding_box(&self) -> AABB {
+        let (a, b, k) = self.axes;
+        let mut min = Point::default();
+        let mut max = Point::default();
+        min[a] = self.a0;
+        max[a] = self.a1;
+        min[b] = self.b0;
+        max[b] = self.b1;
+        min[k] = self.k - PAD;
+        max[k] = self.k + PAD;
+        AABB::new(min, max)
+    }
+}
+
+// Rectangle facing +z/-z at z = k.
+#[derive(Clone)]
+pub struct XYRect(AxisRect);
+
+// Rectangle facing +y/-y at y = k.
+#[derive(Clone)]
+pub struct XZRect(AxisRect);
+
+// Rectangle facing +x/-x at x = k.
+#[derive(Clone)]
+pub struct YZRect(AxisRect);
+
+impl XYRect {
+    pub fn new(x0: f32, x1: f32, y0: f32, y1: f32, k: f32, material: Arc<dyn Material>) -> XYRect {
+        XYRect(AxisRect { axes: (0, 1, 2), a0: x0, a1: x1, b0: y0, b1: y1, k, material })
+    }
+}
+
+impl XZRect {
+    pub fn new(x0: f32, x1: f32, z0: f32, z1: f32, k: f32, material: Arc<dyn Material>) -> XZRect {
+        XZRect(AxisRect { axes: (0, 2, 1), a0: x0, a1: x1, b0: z0, b1: z1, k, material })
+    }
+}
+
+impl YZRect {
+    pub fn new(y0: f32, y1: f32, z0: f32, z1: f32, k: f32, material: Arc<dyn Material>) -> YZRect {
+        YZRect(AxisRect { axes: (1, 2, 0), a0: y0, a1: y1, b0: z0, b1: z1, k, material })
+    }
+}
+
+impl Hittable for XYRect {
+    fn hit(&self, r: Ray, t_min: f32, t_max: f32, _rng: &mut dyn RngCore) -> Option<HitRecord<'_>> {
+        self.0.hit(r, t_min, t_max)
+    }
+
+    fn get_bounding_box(&self) -> Option<AABB> {
+        Some(self.0.bounding_box())
+    }
+}
+
+impl Hittable for XZRect {
+    fn hit(&self, r: Ray, t_min: f32, t_max: f32, _rng: &mut dyn RngCore) -> Option<HitRecord<'_>> {
+        self.0.hit(r, t_min, t_max)
+    }
+
+    fn get_bounding_box(&self) -> Option<AABB> {
+        Some(self.0.bounding_box())
+    }
+}
+
+impl Hittable for YZRect {
+    fn hit(&self, r: Ray, t_min: f32, t_max: f32, _rng: &mut dyn RngCore) -> Option<HitRecord<'_>> {
+        self.0.hit(r, t_min, t_max)
+    }
+
+    fn get_bounding_box(&self) -> Option<AABB> {
+        Some(self.0.bounding_box())
+    }
+}
+
+#[cfg(test)]
+fn grey() -> Arc<dyn Material> {
+    Arc::new(crate::material::Lambertian::new(Point::new(0.5, 0.5, 0.5)))
+}
+
+#[test]
+fn hits_inside_and_misses_outside() {
+    let mut rng = crate::random::Pcg32::new(7, 0);
+    let rect = XZRect::new(-1.0, 1.0, -2.0, 2.0, 3.0, grey());
+    let up = Point::new(0.0, 1.0, 0.0);
+
+    let rec = rect.hit(Ray::new(Point::new(0.5, 0.0, 1.5), up), 0.001, f32::INFINITY, &mut rng).unwrap();
+    assert!((rec.t - 3.0).abs() < 1e-6);
+    assert_eq!(rec.normal, Point::new(0.0, -1.0, 0.0));
+    assert!(!rec.front_face);
+    assert!((rec.u - 0.75).abs() < 1e-6 && (rec.v - 0.875).abs() < 1e-6);
+
+    assert!(rect.hit(Ray::new(Point::new(1.5, 0.0, 0.0), up), 0.001, f32::INFINITY, &mut rng).is_none());
+    assert!(rect.hit(Ray::new(Point::new(0.0, 0.0, 2.5), up), 0.001, f32::INFINITY, &mut rng).is_none());
+    assert!(rect.hit(Ray::new(Point::new(0.0, 0.0, 0.0), up), 0.001, 2.0, &mut rng).is_none());
+    assert!(rect.hit(Ray::new(Point::new(0.0, 0.0, 0.0), Point::new(1.0, 0.0, 0.0)), 0.001, f32::INFINITY, &mut rng).is_none());
+}
+
+#[test]
+fn each_orientation_faces_its_axis() {
+    let mut rng = crate::random::Pcg32::new(7, 0);
+    let rects: [(Box<dyn Hittable>, Point); 3] = [
+        (Box::new(XYRect::new(-1.0, 1.0, -1.0, 1.0, -2.0, grey())), Point::new(0.0, 0.0, 1.0)),
+        (Box::new(XZRect::new(-1.0, 1.0, -1.0, 1.0, -2.0, grey())), Point::new(0.0, 1.0, 0.0)),
+        (Box::new(YZRect::new(-1.0, 1.0, -1.0, 1.0, -2.0, grey())), Point::new(1.0, 0.0, 0.0)),
+    ];
+    for (rect, axis) in rects {
+        let rec = rect.hit(Ray::new(Point::default(), -1.0 * axis), 0.001, f32::INFINITY, &mut rng).unwrap();
+        assert!((rec.t - 2.0).abs() < 1e-6);
+        assert_eq!(rec.normal, axis);
+        assert!(rec.front_face);
+
+        let bounding_box = rect.get_bounding_box().unwrap();
+        assert!(bounding_box.hit_box(Ray::new(Point::default(), -1.0 * axis), 0.001, f32::INFINITY));
+        assert!((bounding_box.max - bounding_box.min).length() > 2.0);
+    }
+}
@@ -0,0 +1,366 @@
+use std::ops::Range;
+
+use crate::accumulator::Accumulator;
+use crate::camera::{Camera, Cast};
+use crate::framebuffer::Framebuffer;
+use crate::hittable_list::CheckHits;
+use crate::image::Image;
+use crate::point::Point;
+use crate::ray::Ray;
+use crate::render_settings::RenderSettings;
+use crate::row_data::RowData;
+use crate::sampler::Sampler;
+use crate::scene::Scene;
+use crate::tiles::{render_tiles, split_into_tiles, TILE_SIZE};
+use crate::utility::luminance;
+
+// In adaptive mode every pixel gets at least this many samples before its
+// noise is trusted, and none gets more than ADAPTIVE_MAX_FACTOR times
+// samples_per_pixel, so a few fireflies can't eat the whole budget.
+const ADAPTIVE_MIN_SAMPLES: u32 = 16;
+const ADAPTIVE_MAX_FACTOR: u32 = 4;
+
+// Averages `counts[p]` samples of each pixel p on the row, numbered on from
+// `first[p]`. Both are indexed like the image. Samples are numbered across the
+// whole render, so splitting it into passes draws exactly the same ones.
+pub fn row_color(row_j: u32, columns: Range<u32>, first: &[u32], counts: &[u32], scene: &Scene, camera: &Camera, settings: &RenderSettings) -> RowData {
+    let pixel_index = row_j * settings.width + columns.start;
+    let mut row_data = RowData::new(pixel_index, columns.len() as u32);
+
+    let mut sampler = settings.sampler.create(settings.seed, settings.strata_count());
+    for pixel_i in columns {
+        let p = (row_j * settings.width + pixel_i) as usize;
+        let mut color = Point::new(0.0, 0.0, 0.0);
+        let mut squares = 0.0;
+        for index in first[p]..first[p] + counts[p] {
+            sampler.start_sample(pixel_i, row_j, index);
+            let [du, dv] = sampler.next_2d();
+            let u = (pixel_i as f32 + du) / settings.width as f32;
+            // Rows run top to bottom but v runs up the viewport.
+            let v = ((settings.height - 1 - row_j) as f32 + dv) / settings.height as f32;
+            let r = camera.get_ray(u, v, sampler.as_mut());
+            let sample = ray_color(r, scene, settings.depth, sampler.as_mut());
+            color = color + sample;
+            squares += luminance(sample) * luminance(sample);
+        }
+        row_data.push_samples(color, squares, counts[p]);
+    }
+    row_data
+}
+
+pub fn ray_color(r: Ray, scene: &Scene, depth: i32, sampler: &mut dyn Sampler) -> Point {
+    if depth <= 0 { return Point::new(0.0, 0.0, 0.0); }
+    if let Some(rec) = scene.world.get_hits(r, 0.001, f32::INFINITY, sampler.rng()) {
+        let emitted = rec.material.emitted(&rec);
+        if let Some(scatter_results) = rec.material.scatter(r, &rec, sampler) {
+            let scattered_ray = scatter_results.ray_dir;
+            let attenuation = scatter_results.attenuation;
+            return emitted + attenuation * ray_color(scattered_ray, scene, depth - 1, sampler);
+        } else {
+            return emitted;
+        }
+    }
+    scene.sky.color(r)
+}
+
+/// Renders `scene` at the resolution and quality given by `settings`. The
+/// scene's camera is refit to the settings' aspect ratio.
+pub fn render(scene: &Scene, settings: &RenderSettings) -> Image {
+    render_hdr(scene, settings).to_image(&settings.tonemap)
+}
+
+/// Same as `render`, calling `on_progress(samples_done, samples_total)` on the
+/// calling thread each time a tile finishes.
+pub fn render_with_progress<F>(scene: &Scene, settings: &RenderSettings, on_progress: F) -> Image
+    where F: FnMut(usize, usize)
+{
+    render_hdr_with_progress(scene, settings, on_progress).to_image(&settings.tonemap)
+}
+
+/// Renders to a linear floating point framebuffer, before any tonemapping.
+pub fn render_hdr(scene: &Scene, settings: &RenderSettings) -> Framebuffer {
+    render_hdr_with_progress(scene, settings, |_, _| {})
+}
+
+/// Same as `render_hdr`, with progress reported like `render_with_progress`.
+pub fn render_hdr_with_progress<F>(scene: &Scene, settings: &RenderSettings, on_progress: F) -> Framebuffer
+    where F: FnMut(usize, usize)
+{
+    let accumulator = Accumulator::new(settings.width, settings.height);
+    let samples = settings.samples_per_pixel as u32;
+    render_progressive(scene, settings, accumulator, samples, on_progress, |_, _| {}).to_framebuffer()
+}
+
+/// Carries `accumulator` on in passes of up to `samples_per_pass` samples per
+/// pixel, calling `on_pass(accumulator, passes_done)` on the calling thread
+/// after every pass to look at the image so far. Start from an empty
+/// accumulator or one saved by an earlier render of the same scene and
+/// settings.
+///
+/// Normally every pixel ends up with `settings.samples_per_pixel`, and the sum
+/// comes out the same (up to rounding) however the samples are split into
+/// passes. With `settings.adaptive` pixels stop once their relative error is
+/// below it, and what they leave of the overall budget goes to the noisy ones.
+pub fn render_progressive<F, G>(scene: &Scene, settings: &RenderSettings, mut accumulator: Accumulator,
+                                samples_per_pass: u32, mut on_progress: F, mut on_pass: G) -> Accumulator
+    where F: FnMut(usize, usize),
+          G: FnMut(&Accumulator, u32)
+{
+    let camera = scene.camera.with_aspect_ratio(settings.aspect_ratio());
+    let total = remaining_samples(&accumulator, settings);
+    let mut done = 0;
+    let mut passes = 0;
+
+    loop {
+        let counts = pass_counts(&accumulator, settings, samples_per_pass.max(1));
+        if counts.iter().all(|&count| count == 0) {
+            break;
+        }
+        let before = done;
+        render_pass(scene, &camera, settings, &counts, &mut accumulator, |pass_done| {
+            done = before + pass_done;
+            on_progress(done.min(total), total);
+        });
+        passes += 1;
+        on_pass(&accumulator, passes);
+    }
+    accumulator
+}
+
+/// Samples `render_progressive` has left to take for all pixels together. In
+/// adaptive mode it may finish with fewer.
+pub fn remaining_samples(accumulator: &Accumulator, settings: &RenderSettings) -> usize {
+    let samples_per_pixel = settings.samples_per_pixel as usize;
+    match settings.adaptive {
+        None => accumulator.samples.iter().map(|&n| samples_per_pixel.saturating_sub(n as usize)).sum(),
+        Some(_) => {
+            let spent: usize = accumulator.samples.iter().map(|&n| n as usize).sum();
+            (samples_per_pixel * accumulator.samples.len()).saturating_sub(spent)
+        }
+    }
+}
+
+// How many samples each pixel gets in the next pass. All zero once done.
+fn pass_counts(accumulator: &Accumulator, settings: &RenderSettings, samples_per_pass: u32) -> Vec<u32> {
+    let samples_per_pixel = settings.samples_per_pixel as u32;
+    let Some(target_error) = settings.adaptive else {
+        return accumulator.samples.iter().map(|&n| samples_per_pixel.saturating_sub(n).min(samples_per_pass)).collect();
+    };
+    if remaining_samples(accumulator, settings) == 0 {
+        return vec![0; accumulator.samples.len()];
+    }
+    let least = ADAPTIVE_MIN_SAMPLES.min(samples_per_pixel);
+    let most = ADAPTIVE_MAX_FACTOR * samples_per_pixel;
+    accumulator.samples.iter().enumerate().map(|(p, &n)| {
+        if n < least {
+            (least - n).min(samples_per_pass)
+        } else if n < most && accumulator.relative_error(p) > target_error {
+            (most - n).min(samples_per_pass)
+        } else {
+            0
+        }
+    }).collect()
+}
+
+/// Adds `counts[p]` more samples to each pixel p of `accumulator`, calling
+/// `on_progress(samples_done)` each time a tile finishes. `camera` should
+/// already fit the settings' aspect ratio.
+pub fn render_pass<F>(scene: &Scene, camera: &Camera, settings: &RenderSettings, counts: &[u32],
+                      accumulator: &mut Accumulator, mut on_progress: F)
+    where F: FnMut(usize)
+{
+    let first = accumulator.samples.clone();
+    let mut done = 0;
+    let tiles = split_into_tiles(settings.width, settings.height, TILE_SIZE);
+    render_tiles(&tiles, settings.threads, |tile| {
+        (tile.y..tile.y + tile.height)
+            .map(|row_j| row_color(row_j, tile.x..tile.x + tile.width, &first, counts, scene, camera, settings))
+            .collect::<Vec<_>>()
+    }, |rows| {
+        for row_data in &rows {
+            accumulator.add_row(row_data);
+            done += row_data.samples.iter().map(|&n| n as usize).sum::<usize>();
+        }
+        on_progress(done);
+    });
+}
+
+#[cfg(test)]
+#[test]
+fn renders_at_the_requested_size() {
+    let scene = Scene::parse("material red lambertian albedo=1,0,0\nsphere center=0,0,-1 radius=0.5 material=red").unwrap();
+    let settings = RenderSettings {
+        width: 40,
+        height: 10,
+        samples_per_pixel: 2,
+        depth: 4,
+        threads: 3,
+        ..RenderSettings::default()
+    };
+    let image = render(&scene, &settings);
+    assert_eq!((image.width, image.height), (40, 10));
+    assert_eq!(image.data.len(), 40 * 10 * 3);
+}
+
+#[test]
+fn sky_is_bluest_along_the_top_row() {
+    let settings = RenderSettings {
+        width: 8,
+        height: 8,
+        samples_per_pixel: 4,
+        depth: 2,
+        ..RenderSettings::default()
+    };
+    let image = render(&Scene::parse("").unwrap(), &settings);
+    let blueness = |row: usize| -> i32 {
+        image.data[row * 24..row * 24 + 24].chunks(3).map(|rgb| rgb[2] as i32 - rgb[0] as i32).sum()
+    };
+    assert!(blueness(0) > blueness(7), "{} {}", blueness(0), blueness(7));
+}
+
+#[test]
+fn bright_sky_is_kept_in_the_hdr_framebuffer() {
+    let mut scene = Scene::parse("").unwrap();
+    scene.sky = crate::Sky::new(Point::new(4.0, 4.0, 4.0), Point::new(4.0, 4.0, 4.0));
+    let settings = RenderSettings {
+        width: 4,
+        height: 4,
+        samples_per_pixel: 1,
+        depth: 2,
+        threads: 1,
+        ..RenderSettings::default()
+    };
+    let framebuffer = render_hdr(&scene, &settings);
+    assert!(framebuffer.pixels.iter().all(|p| (p[0] - 4.0).abs() < 1e-4));
+}
+
+#[test]
+fn enclosed_room_is_lit_only_by_its_light() {
+    let room = "sky off\n\
+                material white lambertian albedo=0.8,0.8,0.8\n\
+                material lamp light emit=4,4,4\n\
+                camera lookfrom=0,0,0 lookat=0,0,-1 aperture=0\n\
+                sphere center=0,0,0 radius=5 material=white\n";
+    let settings = RenderSettings {
+        width: 8,
+        height: 8,
+        samples_per_pixel: 8,
+        depth: 8,
+        threads: 1,
+        ..RenderSettings::default()
+    };
+    let dark = render_hdr(&Scene::parse(room).unwrap(), &settings);
+    assert!(dark.pixels.iter().all(|&p| p == Point::new(0.0, 0.0, 0.0)));
+
+    let lit = render_hdr(&Scene::parse(&format!("{}sphere center=0,2,-2 radius=0.5 material=lamp", room)).unwrap(), &settings);
+    let total: f32 = lit.pixels.iter().map(|p| p[0]).sum();
+    assert!(total > 0.0);
+}
+
+#[test]
+fn same_seed_renders_the_same_image_on_any_thread_count() {
+    let scene = Scene::parse("render seed=11\n\
+                              camera aperture=0.5\n\
+                              material glass dielectric ir=1.5\n\
+                              material tin metal albedo=0.8,0.8,0.8 fuzz=0.3\n\
+                              material red lambertian albedo=1,0,0\n\
+                              sphere center=-1,0,-1 radius=0.5 material=glass\n\
+                              sphere center=0,0,-1 radius=0.5 material=tin\n\
+                              sphere center=1,0,-1 radius=0.5 material=red\n").unwrap();
+    assert_eq!(scene.settings.seed, 11);
+    let mut settings = RenderSettings {
+        width: 37,
+        height: 21,
+        samples_per_pixel: 3,
+        depth: 6,
+        threads: 1,
+        seed: scene.settings.seed,
+        sampler: crate::SamplerKind::Sobol,
+        ..RenderSettings::default()
+    };
+    let single = render_hdr(&scene, &settings);
+    settings.threads = 4;
+    assert_eq!(render_hdr(&scene, &settings).pixels, single.pixels);
+    settings.seed += 1;
+    assert_ne!(render_hdr(&scene, &settings).pixels, single.pixels);
+}
+
+#[test]
+fn passes_add_up_to_the_whole_render() {
+    let scene = Scene::parse("camera aperture=0.3\n\
+                              material tin metal albedo=0.8,0.6,0.4 fuzz=0.4\n\
+                              sphere center=0,0,-1 radius=0.5 material=tin\n").unwrap();
+    let settings = RenderSettings {
+        width: 12,
+        height: 9,
+        samples_per_pixel: 7,
+        depth: 4,
+        threads: 2,
+        seed: 5,
+        sampler: crate::SamplerKind::Stratified,
+        ..RenderSettings::default()
+    };
+    let whole = render_hdr(&scene, &settings);
+
+    let mut seen = Vec::new();
+    let mut last_progress = (0, 0);
+    let progressive = render_progressive(&scene, &settings, Accumulator::new(12, 9), 3, |done, total| last_progress = (done, total), |accumulator, passes| {
+        seen.push((passes, accumulator.samples[0]));
+    });
+    assert_eq!(seen, [(1, 3), (2, 6), (3, 7)]);
+    assert_eq!(last_progress, (12 * 9 * 7, 12 * 9 * 7));
+    assert!(progressive.samples.iter().all(|&n| n == 7));
+    for (a, b) in progressive.to_framebuffer().pixels.iter().zip(&whole.pixels) {
+        assert!((*a - *b).length() < 1e-4 * (1.0 + b.length()), "{:?} {:?}", a, b);
+    }
+
+    // Stopping after two passes and resuming draws the same last sample.
+    let mut stopped = None;
+    render_progressive(&scene, &settings, Accumulator::new(12, 9), 3, |_, _| {}, |accumulator, passes| {
+        if passes == 2 {
+            stopped = Some(accumulator.clone());
+        }
+    });
+    let stopped = stopped.unwrap();
+    let mut passes_left = 0;
+    let resumed = render_progressive(&scene, &settings, stopped, 3, |_, _| {}, |_, passes| passes_left = passes);
+    assert_eq!(passes_left, 1);
+    for (a, b) in resumed.to_framebuffer().pixels.iter().zip(&whole.pixels) {
+        assert!((*a - *b).length() < 1e-4 * (1.0 + b.length()), "{:?} {:?}", a, b);
+    }
+    assert_eq!(remaining_samples(&resumed, &settings), 0);
+}
+
+#[test]
+fn adaptive_sampling_spends_more_on_noisy_pixels() {
+    // Flat sky on the left half, a rough metal ball filling the right.
+    let scene = Scene::parse("sky color=0.5,0.5,0.5\n\
+                              camera lookfrom=0,0,0 lookat=0,0,-1 vfov=60 aperture=0\n\
+                              material rough metal albedo=0.9,0.9,0.9 fuzz=1\n\
+                              material white lambertian albedo=0.8,0.8,0.8\n\
+                              sphere center=1.5,0,-2 radius=1.2 material=rough\n\
+                              sphere center=1.5,-101.2,-2 radius=100 material=white\n").unwrap();
+    let settings = RenderSettings {
+        width: 16,
+        height: 8,
+        samples_per_pixel: 32,
+        depth: 8,
+        threads: 2,
+        seed: 1,
+        adaptive: Some(0.02),
+        ..RenderSettings::default()
+    };
+    let accumulator = render_progressive(&scene, &settings, Accumulator::new(16, 8), 4, |_, _| {}, |_, _| {});
+    let samples = &accumulator.samples;
+    // The sky stops as early as allowed and the ball makes up for it, without
+    // going over the budget by more than a pass.
+    assert_eq!(samples[0], ADAPTIVE_MIN_SAMPLES);
+    assert!(samples.iter().all(|&n| (ADAPTIVE_MIN_SAMPLES..=4 * 32).contains(&n)));
+    assert!(samples.iter().any(|&n| n > 32));
+    let spent: u32 = samples.iter().sum();
+    assert!(spent <= 16 * 8 * (32 + 4), "{}", spent);
+
+    let uniform = RenderSettings { adaptive: None, ..settings };
+    assert_eq!(remaining_samples(&Accumulator::new(16, 8), &uniform), 16 * 8 * 32);
+    assert_eq!(remaining_samples(&accumulator, &settings), (16 * 8 * 32usize).saturating_sub(spent as usize));
+}
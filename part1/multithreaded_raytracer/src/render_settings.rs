@@ -0,0 +1,51 @@
+use std::thread;
+
+use crate::sampler::SamplerKind;
+use crate::tonemap::Tonemap;
+
+#[derive(Clone, Copy, Debug)]
+pub struct RenderSettings {
+    pub width: u32,
+    pub height: u32,
+    pub samples_per_pixel: i32,
+    pub depth: i32,
+    pub threads: usize,
+    pub tonemap: Tonemap,
+    // Same seed, same image, whatever the thread count.
+    pub seed: u64,
+    pub sampler: SamplerKind,
+    // Stop sampling a pixel once the standard error of its mean is this
+    // fraction of the mean, spending the samples saved on noisier pixels.
+    // None gives every pixel samples_per_pixel.
+    pub adaptive: Option<f32>,
+    // How many strata the stratified sampler splits a pixel into. None means
+    // samples_per_pixel; a resumed render keeps the count it started with.
+    pub strata: Option<u32>,
+}
+
+impl RenderSettings {
+    pub fn aspect_ratio(&self) -> f32 {
+        self.width as f32 / self.height as f32
+    }
+
+    pub fn strata_count(&self) -> u32 {
+        self.strata.unwrap_or(self.samples_per_pixel as u32)
+    }
+}
+
+impl Default for RenderSettings {
+    fn default() -> Self {
+        RenderSettings {
+            width: 1600,
+            height: 900,
+            samples_per_pixel: 300,
+            depth: 500,
+            threads: thread::available_parallelism().map_or(1, |n| n.get()),
+            tonemap: Tonemap::default(),
+            seed: 0,
+            sampler: SamplerKind::Independent,
+            adaptive: None,
+            strata: None,
+        }
+    }
+}
@@ -1,74 +1,71 @@
-use crate::{IMAGE_WIDTH, Point};
-use crate::utility::clamp;
+use crate::Point;
 
+// Linear radiance for a run of pixels on one row, averaged over samples.
+// Alongside each color goes how many samples it took and the sum of their
+// squared luminances, for telling how noisy the pixel still is.
 pub struct RowData {
     pub index: u32,
-    pub rbg_values: [u8; (IMAGE_WIDTH * 3) as usize],
-    i: usize,
+    pub colors: Vec<Point>,
+    pub samples: Vec<u32>,
+    pub squares: Vec<f32>,
 }
 
 impl RowData {
-    pub fn new(index: u32) -> RowData {
+    pub fn new(index: u32, width: u32) -> RowData {
         RowData {
             index,
-            rbg_values: [255; (IMAGE_WIDTH * 3) as usize],
-            i: 0,
+            colors: Vec::with_capacity(width as usize),
+            samples: Vec::with_capacity(width as usize),
+            squares: Vec::with_capacity(width as usize),
         }
     }
 
     pub fn push_color(&mut self, rgb_point: Point, samples_per_pixel: i32) {
-        let scale = 1.0 / samples_per_pixel as f32;
-        let r = (scale * rgb_point[0]).sqrt(); // Gamma correction
-        let g = (scale * rgb_point[1]).sqrt();
-        let b = (scale * rgb_point[2]).sqrt();
-
-        let new_r = (255.0 * clamp(r, 0.0, 1.0)) as u8;
-        let new_g = (255.0 * clamp(g, 0.0, 1.0)) as u8;
-        let new_b = (255.0 * clamp(b, 0.0, 1.0)) as u8;
+        self.push_samples(rgb_point, 0.0, samples_per_pixel as u32);
+    }
 
-        self.rbg_values[self.i] = new_r;
-        self.rbg_values[self.i + 1] = new_g;
-        self.rbg_values[self.i + 2] = new_b;
-        self.i += 3;
+    // A pixel given `samples` samples adding up to `sum`, and `squares` for
+    // their luminances. A pixel skipped with no samples comes out black.
+    pub fn push_samples(&mut self, sum: Point, squares: f32, samples: u32) {
+        let scale = if samples > 0 { 1.0 / samples as f32 } else { 0.0 };
+        self.colors.push(scale * sum);
+        self.samples.push(samples);
+        self.squares.push(squares);
     }
 }
 
 #[cfg(test)]
 #[test]
 fn iter_self() {
-    let mut cd = RowData::new(66);
+    let mut cd = RowData::new(66, 400);
     let red = Point::new(1.0, 0.0, 0.0);
     let blue = Point::new(0.0, 1.0, 0.0);
     let green = Point::new(0.0, 0.0, 1.0);
 
     cd.push_color(red, 1);
-    assert_eq!(cd.rbg_values[0], 255);
-    assert_eq!(cd.rbg_values[1], 0);
-    assert_eq!(cd.rbg_values[2], 0);
-    assert_eq!(cd.rbg_values[3], 0);
+    assert_eq!(cd.colors, [red]);
 
     cd.push_color(blue, 1);
-    assert_eq!(cd.rbg_values[0], 255);
-    assert_eq!(cd.rbg_values[1], 0);
-    assert_eq!(cd.rbg_values[2], 0);
-
-    assert_eq!(cd.rbg_values[3], 0);
-    assert_eq!(cd.rbg_values[4], 255);
-    assert_eq!(cd.rbg_values[5], 0);
-
-    assert_eq!(cd.rbg_values[6], 0);
+    assert_eq!(cd.colors, [red, blue]);
 
     cd.push_color(green, 1);
+    assert_eq!(cd.colors, [red, blue, green]);
+}
 
-    assert_eq!(cd.rbg_values[0], 255);
-    assert_eq!(cd.rbg_values[1], 0);
-    assert_eq!(cd.rbg_values[2], 0);
-
-    assert_eq!(cd.rbg_values[3], 0);
-    assert_eq!(cd.rbg_values[4], 255);
-    assert_eq!(cd.rbg_values[5], 0);
+#[test]
+fn averages_samples_without_clamping() {
+    let mut cd = RowData::new(0, 1);
+    cd.push_color(Point::new(12.0, 4.0, 0.0), 4);
+    assert_eq!(cd.colors, [Point::new(3.0, 1.0, 0.0)]);
+    assert_eq!(cd.samples, [4]);
+}
 
-    assert_eq!(cd.rbg_values[6], 0);
-    assert_eq!(cd.rbg_values[7], 0);
-    assert_eq!(cd.rbg_values[8], 255);
-}
\ No newline at end of file
+#[test]
+fn skipped_pixels_are_black() {
+    let mut cd = RowData::new(0, 2);
+    cd.push_samples(Point::new(2.0, 2.0, 2.0), 3.0, 2);
+    cd.push_samples(Point::default(), 0.0, 0);
+    assert_eq!(cd.colors, [Point::new(1.0, 1.0, 1.0), Point::default()]);
+    assert_eq!(cd.samples, [2, 0]);
+    assert_eq!(cd.squares, [3.0, 0.0]);
+}
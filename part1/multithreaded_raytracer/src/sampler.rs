@@ -0,0 +1,402 @@
+use rand::{Rng, RngCore};
+
+use crate::random::{mix, Pcg32};
+
+// Which sample pattern a render draws from.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum SamplerKind {
+    // Plain uniform random numbers.
+    Independent,
+    // One jittered sample per cell of a grid with samples_per_pixel cells.
+    Stratified,
+    // Radical inverses in successive prime bases, randomly shifted per pixel.
+    Halton,
+    // Sobol (0,2)-sequence with hash-based Owen scrambling.
+    Sobol,
+}
+
+impl SamplerKind {
+    pub fn from_name(name: &str) -> Option<SamplerKind> {
+        match name.to_ascii_lowercase().as_str() {
+            "independent" => Some(SamplerKind::Independent),
+            "stratified" => Some(SamplerKind::Stratified),
+            "halton" => Some(SamplerKind::Halton),
+            "sobol" => Some(SamplerKind::Sobol),
+            _ => None,
+        }
+    }
+
+    // A sampler for `samples_per_pixel` samples in every pixel. Each render
+    // thread makes its own.
+    pub fn create(self, seed: u64, samples_per_pixel: u32) -> Box<dyn Sampler> {
+        let state = SampleState::new(seed);
+        match self {
+            SamplerKind::Independent => Box::new(IndependentSampler { state }),
+            SamplerKind::Stratified => Box::new(StratifiedSampler { state, samples_per_pixel: samples_per_pixel.max(1) }),
+            SamplerKind::Halton => Box::new(HaltonSampler { state }),
+            SamplerKind::Sobol => Box::new(SobolSampler { state }),
+        }
+    }
+}
+
+// Hands out the numbers one camera sample needs: the pixel offset, the lens
+// position, the shutter time and then a few per bounce, each 1D or 2D request
+// being its own dimension of the pattern. Whatever doesn't need structure,
+// like rejection loops, draws from `rng`.
+pub trait Sampler: Send {
+    // Starts sample number `index` of pixel (x, y), back at the first dimension.
+    fn start_sample(&mut self, x: u32, y: u32, index: u32);
+    fn next_1d(&mut self) -> f32;
+    fn next_2d(&mut self) -> [f32; 2];
+    fn rng(&mut self) -> &mut dyn RngCore;
+}
+
+// What every sampler keeps about the sample in progress.
+struct SampleState {
+    seed: u64,
+    // Fixed for all samples of a pixel, so its scrambles line up.
+    pixel_hash: u64,
+    index: u32,
+    dimension: u32,
+    rng: Pcg32,
+}
+
+impl SampleState {
+    fn new(seed: u64) -> SampleState {
+        SampleState {
+            seed,
+            pixel_hash: 0,
+            index: 0,
+            dimension: 0,
+            rng: Pcg32::for_sample(seed, 0, 0, 0),
+        }
+    }
+
+    fn start(&mut self, x: u32, y: u32, index: u32) {
+        self.pixel_hash = mix(self.seed ^ mix((y as u64) << 32 | x as u64));
+        self.index = index;
+        self.dimension = 0;
+        self.rng = Pcg32::for_sample(self.seed, x, y, index);
+    }
+
+    // A hash for the next dimension of this pixel.
+    fn next_dimension(&mut self) -> (u32, u32) {
+        let dimension = self.dimension;
+        self.dimension += 1;
+        (dimension, mix(self.pixel_hash ^ dimension as u64) as u32)
+    }
+}
+
+pub struct IndependentSampler {
+    state: SampleState,
+}
+
+impl Sampler for IndependentSampler {
+    fn start_sample(&mut self, x: u32, y: u32, index: u32) {
+        self.state.start(x, y, index);
+    }
+
+    fn next_1d(&mut self) -> f32 {
+        self.state.rng.gen()
+    }
+
+    fn next_2d(&mut self) -> [f32; 2] {
+        [self.state.rng.gen(), self.state.rng.gen()]
+    }
+
+    fn rng(&mut self) -> &mut dyn RngCore {
+        &mut self.state.rng
+    }
+}
+
+pub struct StratifiedSampler {
+    state: SampleState,
+    samples_per_pixel: u32,
+}
+
+impl Sampler for StratifiedSampler {
+    fn start_sample(&mut self, x: u32, y: u32, index: u32) {
+        self.state.start(x, y, index);
+    }
+
+    fn next_1d(&mut self) -> f32 {
+        let (_, hash) = self.state.next_dimension();
+        let n = self.samples_per_pixel;
+        // Samples past the count start another, differently shuffled round.
+        let stratum = permute(self.state.index % n, n, hash ^ (self.state.index / n));
+        (stratum as f32 + self.state.rng.gen::<f32>()) / n as f32
+    }
+
+    fn next_2d(&mut self) -> [f32; 2] {
+        let (_, hash) = self.state.next_dimension();
+        let n = self.samples_per_pixel;
+        let columns = (n as f32).sqrt() as u32;
+        let rows = n.div_ceil(columns);
+        let cell = permute(self.state.index % n, columns * rows, hash ^ (self.state.index / n));
+        [((cell % columns) as f32 + self.state.rng.gen::<f32>()) / columns as f32,
+         ((cell / columns) as f32 + self.state.rng.gen::<f32>()) / rows as f32]
+    }
+
+    fn rng(&mut self) -> &mut dyn RngCore {
+        &mut self.state.rng
+    }
+}
+
+const PRIMES: [u32; 32] = [2, 3, 5, 7, 11, 13, 17, 19, 23, 29, 31, 37, 41, 43, 47, 53,
+                           59, 61, 67, 71, 73, 79, 83, 89, 97, 101, 103, 107, 109, 113, 127, 131];
+
+pub struct HaltonSampler {
+    state: SampleState,
+}
+
+impl HaltonSampler {
+    // Past the last prime the bases get too large to be worth it, so deep
+    // bounces fall back to random numbers.
+    fn next(&mut self) -> f32 {
+        let (dimension, hash) = self.state.next_dimension();
+        match PRIMES.get(dimension as usize) {
+            Some(&base) => {
+                let shift = (hash >> 8) as f32 / (1 << 24) as f32;
+                let x = radical_inverse(base, self.state.index) + shift;
+                if x >= 1.0 { x - 1.0 } else { x }
+            }
+            None => self.state.rng.gen(),
+        }
+    }
+}
+
+impl Sampler for HaltonSampler {
+    fn start_sample(&mut self, x: u32, y: u32, index: u32) {
+        self.state.start(x, y, index);
+    }
+
+    fn next_1d(&mut self) -> f32 {
+        self.next()
+    }
+
+    fn next_2d(&mut self) -> [f32; 2] {
+        [self.next(), self.next()]
+    }
+
+    fn rng(&mut self) -> &mut dyn RngCore {
+        &mut self.state.rng
+    }
+}
+
+// Every 1D or 2D request uses the first one or two Sobol dimensions, with the
+// sample index shuffled and the points scrambled by their own hash. That keeps
+// each pair stratified while decorrelating it from the others (Burley 2020,
+// "Practical Hash-based Owen Scrambling").
+pub struct SobolSampler {
+    state: SampleState,
+}
+
+impl Sampler for SobolSampler {
+    fn start_sample(&mut self, x: u32, y: u32, index: u32) {
+        self.state.start(x, y, index);
+    }
+
+    fn next_1d(&mut self) -> f32 {
+        let (_, hash) = self.state.next_dimension();
+        let index = nested_uniform_scramble(self.state.index, hash);
+        to_unit(nested_uniform_scramble(sobol(index, 0), mix(hash as u64 ^ 1) as u32))
+    }
+
+    fn next_2d(&mut self) -> [f32; 2] {
+        let (_, hash) = self.state.next_dimension();
+        let index = nested_uniform_scramble(self.state.index, hash);
+        [to_unit(nested_uniform_scramble(sobol(index, 0), mix(hash as u64 ^ 1) as u32)),
+         to_unit(nested_uniform_scramble(sobol(index, 1), mix(hash as u64 ^ 2) as u32))]
+    }
+
+    fn rng(&mut self) -> &mut dyn RngCore {
+        &mut self.state.rng
+    }
+}
+
+// The top 24 bits as a float in 0..1, never rounding up to 1.
+fn to_unit(bits: u32) -> f32 {
+    (bits >> 8) as f32 / (1 << 24) as f32
+}
+
+// Mirrors the digits of `index` in `base` around the radix point.
+pub fn radical_inverse(base: u32, mut index: u32) -> f32 {
+    let inverse_base = 1.0 / base as f64;
+    let mut digits = 0u64;
+    let mut scale = 1.0;
+    while index > 0 {
+        digits = digits * base as u64 + (index % base) as u64;
+        scale *= inverse_base;
+        index /= base;
+    }
+    ((digits as f64 * scale) as f32).min(1.0 - f32::EPSILON / 2.0)
+}
+
+// First two dimensions of the Sobol sequence as 32-bit fractions. The first is
+// the van der Corput sequence; the second comes from the primitive polynomial
+// x + 1, whose direction numbers are each the one before xored with itself
+// shifted right by one.
+pub fn sobol(index: u32, dimension: u32) -> u32 {
+    let mut result = 0;
+    let mut direction = 1u32 << 31;
+    let mut index = index;
+    while index != 0 {
+        if index & 1 != 0 {
+            result ^= direction;
+        }
+        index >>= 1;
+        direction = if dimension == 0 { direction >> 1 } else { direction ^ (direction >> 1) };
+    }
+    result
+}
+
+// An Owen scramble of all 32 bits: each bit is flipped depending on a hash
+// of the bits above it.
+pub fn nested_uniform_scramble(x: u32, seed: u32) -> u32 {
+    laine_karras_permutation(x.reverse_bits(), seed).reverse_bits()
+}
+
+fn laine_karras_permutation(mut x: u32, seed: u32) -> u32 {
+    x = x.wrapping_add(seed);
+    x ^= x.wrapping_mul(0x6c50b47c);
+    x ^= x.wrapping_mul(0xb82f1e52);
+    x ^= x.wrapping_mul(0xc7afe638);
+    x ^= x.wrapping_mul(0x8d22f6e6);
+    x
+}
+
+// Kensler's hashed permutation ("Correlated Multi-Jittered Sampling"): maps
+// 0..length onto itself in an order picked by `seed`, without a table.
+pub fn permute(mut i: u32, length: u32, seed: u32) -> u32 {
+    let mut w = length - 1;
+    w |= w >> 1;
+    w |= w >> 2;
+    w |= w >> 4;
+    w |= w >> 8;
+    w |= w >> 16;
+    loop {
+        i ^= seed;
+        i = i.wrapping_mul(0xe170893d);
+        i ^= seed >> 16;
+        i ^= (i & w) >> 4;
+        i ^= seed >> 8;
+        i = i.wrapping_mul(0x0929eb3f);
+        i ^= seed >> 23;
+        i ^= (i & w) >> 1;
+        i = i.wrapping_mul(1 | seed >> 27);
+        i = i.wrapping_mul(0x6935fa69);
+        i ^= (i & w) >> 11;
+        i = i.wrapping_mul(0x74dcb303);
+        i ^= (i & w) >> 2;
+        i = i.wrapping_mul(0x9e501cc3);
+        i ^= (i & w) >> 2;
+        i = i.wrapping_mul(0xc860a3df);
+        i &= w;
+        i ^= i >> 5;
+        if i < length {
+            break;
+        }
+    }
+    ((i as u64 + seed as u64) % length as u64) as u32
+}
+
+#[cfg(test)]
+fn draw(kind: SamplerKind, samples: u32, dimensions: u32) -> Vec<Vec<[f32; 2]>> {
+    let mut sampler = kind.create(3, samples);
+    (0..samples).map(|index| {
+        sampler.start_sample(5, 9, index);
+        (0..dimensions).map(|_| sampler.next_2d()).collect()
+    }).collect()
+}
+
+// Counts how many points land in each cell of a `columns` by `rows` grid.
+#[cfg(test)]
+fn occupancy(points: impl Iterator<Item = [f32; 2]>, columns: usize, rows: usize) -> Vec<usize> {
+    let mut cells = vec![0; columns * rows];
+    for [x, y] in points {
+        assert!((0.0..1.0).contains(&x) && (0.0..1.0).contains(&y), "{} {}", x, y);
+        cells[(y * rows as f32) as usize * columns + (x * columns as f32) as usize] += 1;
+    }
+    cells
+}
+
+#[test]
+fn sobol_follows_its_generator_matrices() {
+    // Indexed directly rather than in Gray code order, so points 2 and 3 come
+    // out swapped from the usual listing.
+    let points: Vec<(u32, u32)> = (0..4).map(|i| (sobol(i, 0), sobol(i, 1))).collect();
+    assert_eq!(points, vec![(0, 0), (1 << 31, 1 << 31), (1 << 30, 3 << 30), (3 << 30, 1 << 30)]);
+    assert_eq!(radical_inverse(2, 6), 0.375);
+    assert!((radical_inverse(3, 5) - 7.0 / 9.0).abs() < 1e-6);
+}
+
+#[test]
+fn permute_is_a_permutation() {
+    for length in [1, 2, 7, 16, 100] {
+        for seed in [0, 1, 0xdeadbeef] {
+            let mut seen: Vec<u32> = (0..length).map(|i| permute(i, length, seed)).collect();
+            seen.sort();
+            assert_eq!(seen, (0..length).collect::<Vec<_>>());
+        }
+    }
+}
+
+#[test]
+fn scrambled_sobol_stays_a_net_in_every_dimension() {
+    let samples = draw(SamplerKind::Sobol, 64, 4);
+    for dimension in 0..4 {
+        // Every elementary interval of area 1/64 holds exactly one point.
+        for (columns, rows) in [(64, 1), (32, 2), (8, 8), (1, 64)] {
+            let cells = occupancy(samples.iter().map(|s| s[dimension]), columns, rows);
+            assert!(cells.iter().all(|&c| c == 1), "dimension {} {}x{}", dimension, columns, rows);
+        }
+    }
+    // Different dimensions and pixels get different scrambles.
+    assert_ne!(samples[1][0], samples[1][1]);
+    let mut sampler = SamplerKind::Sobol.create(3, 64);
+    sampler.start_sample(6, 9, 1);
+    assert_ne!(sampler.next_2d(), samples[1][0]);
+}
+
+#[test]
+fn stratified_fills_every_cell() {
+    let samples = draw(SamplerKind::Stratified, 16, 3);
+    for dimension in 0..3 {
+        let cells = occupancy(samples.iter().map(|s| s[dimension]), 4, 4);
+        assert!(cells.iter().all(|&c| c == 1));
+    }
+    let mut sampler = SamplerKind::Stratified.create(3, 10);
+    let mut strata: Vec<u32> = (0..10).map(|index| {
+        sampler.start_sample(0, 0, index);
+        (sampler.next_1d() * 10.0) as u32
+    }).collect();
+    strata.sort();
+    assert_eq!(strata, (0..10).collect::<Vec<_>>());
+}
+
+#[test]
+fn halton_is_evenly_spread() {
+    // A shift keeps the first 2^k points of base 2 and 3^k points of base 3
+    // exactly one to a cell.
+    let samples = draw(SamplerKind::Halton, 27, 40);
+    let cells = occupancy(samples[..16].iter().map(|s| s[0]), 16, 1);
+    assert!(cells.iter().all(|&c| c == 1));
+    let cells = occupancy(samples.iter().map(|s| s[0]), 1, 27);
+    assert!(cells.iter().all(|&c| c == 1));
+    // Past the table of primes it keeps going with plain random numbers.
+    occupancy(samples.iter().map(|s| s[39]), 1, 1);
+}
+
+#[test]
+fn samples_repeat_for_the_same_pixel_and_index() {
+    for kind in [SamplerKind::Independent, SamplerKind::Stratified, SamplerKind::Halton, SamplerKind::Sobol] {
+        assert_eq!(draw(kind, 8, 5), draw(kind, 8, 5));
+        let mut sampler = kind.create(3, 8);
+        sampler.start_sample(1, 1, 2);
+        let first = (sampler.next_1d(), sampler.rng().next_u32());
+        sampler.start_sample(1, 1, 2);
+        assert_eq!((sampler.next_1d(), sampler.rng().next_u32()), first, "{:?}", kind);
+    }
+    assert_eq!(SamplerKind::from_name("Sobol"), Some(SamplerKind::Sobol));
+    assert_eq!(SamplerKind::from_name("random"), None);
+}
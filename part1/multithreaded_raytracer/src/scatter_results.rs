@@ -2,7 +2,7 @@ use crate::{Point, Ray};
 
 #[derive(Clone, Copy)]
 pub struct ScatterResults {
-    pub(crate) ray_dir: Ray,
-    pub(crate) norm: Point,
-    pub(crate) attenuation: Point,
+    pub ray_dir: Ray,
+    pub norm: Point,
+    pub attenuation: Point,
 }
\ No newline at end of file
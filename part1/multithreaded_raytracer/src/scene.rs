@@ -0,0 +1,1057 @@
+// Text scene descriptions.
+//
+// One statement per line, '#' starts a comment:
+//
+//   camera lookfrom=0,2,3 lookat=0,0,0 vup=0,1,0 vfov=90 aperture=0.1 focus_dist=10 shutter=0,1
+//   render width=1600 height=900 samples=300 depth=500 tonemap=aces exposure=0.5 seed=1 sampler=sobol adaptive=0.02
+//   sky horizon=0.9,0.9,0.9 zenith=0.5,0.7,1.0
+//   sky color=r,g,b                   (a flat background)
+//   sky off                           (black, light only comes from emitters)
+//   texture <name> solid color=r,g,b
+//   texture <name> checker even=<color> odd=<color> size=f
+//   texture <name> image file=<path.png|path.ppm> wrap=repeat|clamp
+//   texture <name> noise|marble|wood scale=f seed=n low=<color> high=<color>
+//   material <name> lambertian albedo=<color>
+//   material <name> metal albedo=<color> fuzz=f
+//   material <name> dielectric ir=f
+//   material <name> light emit=<color>
+//   material <name> isotropic albedo=<color>
+//   sphere center=x,y,z radius=r material=<name>
+//   moving_sphere center0=x,y,z center1=x,y,z time=t0,t1 radius=r material=<name>
+//   xy_rect x=x0,x1 y=y0,y1 z=k material=<name>
+//   xz_rect x=x0,x1 z=z0,z1 y=k material=<name>
+//   yz_rect y=y0,y1 z=z0,z1 x=k material=<name>
+//   plane point=x,y,z normal=x,y,z material=<name>
+//   box min=x,y,z max=x,y,z material=<name>
+//   mesh file=<path.obj> material=<name>
+//
+// A <color> is either r,g,b or the name of a texture defined above it. The
+// noise textures blend from `low` (black) to `high` (white), and the same seed
+// always gives the same pattern.
+//
+// The camera's shutter is closed (every ray at time 0) unless given, and a
+// moving sphere is at center0 at t0 and center1 at t1, by default 0 and 1.
+//
+// Anything left out of camera, render and sky keeps its default. Mesh and
+// image paths are relative to the scene file, and repeating a mesh with the
+// same material shares one copy of it.
+//
+// Every shape also takes an optional transform, applied as scale, then rotate
+// about x, y and z in degrees, then translate:
+//
+//   box min=0,0,0 max=1,1,1 material=<name> scale=2 rotate=0,45,0 translate=1,0,-3
+//
+// and an optional density, which fills the shape with fog instead of giving it
+// a surface. Its material then picks scatter directions, so use isotropic:
+//
+//   sphere center=0,1,0 radius=1 material=<name> density=0.5
+
+use std::collections::HashMap;
+use std::error::Error;
+use std::fmt;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use crate::camera::Camera;
+use crate::constant_medium::ConstantMedium;
+use crate::cuboid::Cuboid;
+use crate::hittable::Hittable;
+use crate::hittable_list::HittableList;
+use crate::plane::Plane;
+use crate::point::Point;
+use crate::rect::{XYRect, XZRect, YZRect};
+use crate::render_settings::RenderSettings;
+use crate::sampler::SamplerKind;
+use crate::sky::Sky;
+use crate::tonemap::Operator;
+use crate::transform::Transform;
+use crate::material::{Dielectric, DiffuseLight, Isotropic, Lambertian, Material, Metal};
+use crate::matrix::Matrix4;
+use crate::mesh::Mesh;
+use crate::moving_sphere::MovingSphere;
+use crate::sphere::Sphere;
+use crate::texture::{Checker, ImageTexture, NoiseTexture, Pattern, SolidColor, Texture, Wrap};
+use crate::utility::cross;
+
+pub struct Scene {
+    pub world: HittableList,
+    pub camera: Camera,
+    pub sky: Sky,
+    pub settings: RenderSettings,
+}
+
+#[derive(Debug)]
+pub enum SceneError {
+    Io(std::io::Error),
+    Parse {
+        line: usize,
+        column: usize,
+        message: String,
+    },
+}
+
+impl fmt::Display for SceneError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SceneError::Io(err) => write!(f, "{}", err),
+            SceneError::Parse { line, column, message } => write!(f, "{}:{}: {}", line, column, message),
+        }
+    }
+}
+
+impl Error for SceneError {}
+
+impl Scene {
+    pub fn load(path: &Path) -> Result<Scene, SceneError> {
+        let source = fs::read_to_string(path).map_err(SceneError::Io)?;
+        Scene::parse_in(&source, path.parent().unwrap_or(Path::new("")))
+    }
+
+    pub fn parse(source: &str) -> Result<Scene, SceneError> {
+        Scene::parse_in(source, Path::new(""))
+    }
+
+    // Like `parse`, with file references resolved against `base_dir`.
+    pub fn parse_in(source: &str, base_dir: &Path) -> Result<Scene, SceneError> {
+        let mut parser = Parser::new(base_dir.to_path_buf());
+        for (line_i, line) in source.lines().enumerate() {
+            let tokens = tokenize(line_i + 1, line);
+            if let Some((keyword, rest)) = tokens.split_first() {
+                parser.statement(*keyword, rest)?;
+            }
+        }
+        Ok(parser.finish())
+    }
+}
+
+#[derive(Clone, Copy)]
+struct Token<'a> {
+    text: &'a str,
+    line: usize,
+    column: usize,
+}
+
+impl<'a> Token<'a> {
+    fn error(&self, message: impl Into<String>) -> SceneError {
+        SceneError::Parse {
+            line: self.line,
+            column: self.column,
+            message: message.into(),
+        }
+    }
+
+    // Position just past the token, for things that are missing after it.
+    fn end(&self) -> Token<'a> {
+        Token {
+            text: "",
+            line: self.line,
+            column: self.column + self.text.chars().count(),
+        }
+    }
+
+    fn sub_token(&self, byte_offset: usize, text: &'a str) -> Token<'a> {
+        Token {
+            text,
+            line: self.line,
+            column: self.column + self.text[..byte_offset].chars().count(),
+        }
+    }
+}
+
+fn tokenize(line_number: usize, line: &str) -> Vec<Token<'_>> {
+    let line = line.split('#').next().unwrap_or("");
+    let mut tokens = Vec::new();
+    let mut start: Option<(usize, usize)> = None;
+    let mut column = 0;
+    for (byte_i, ch) in line.char_indices() {
+        column += 1;
+        if ch.is_whitespace() {
+            if let Some((token_byte, token_column)) = start.take() {
+                tokens.push(Token { text: &line[token_byte..byte_i], line: line_number, column: token_column });
+            }
+        } else if start.is_none() {
+            start = Some((byte_i, column));
+        }
+    }
+    if let Some((token_byte, token_column)) = start {
+        tokens.push(Token { text: &line[token_byte..], line: line_number, column: token_column });
+    }
+    tokens
+}
+
+fn parse_float(token: Token) -> Result<f32, SceneError> {
+    match token.text.parse::<f32>() {
+        Ok(value) if value.is_finite() => Ok(value),
+        _ => Err(token.error(format!("expected a number, found '{}'", token.text))),
+    }
+}
+
+fn parse_numbers<const N: usize>(token: Token) -> Result<[f32; N], SceneError> {
+    let expected = || {
+        let count = ["no", "one", "two", "three"].get(N).copied().unwrap_or("several");
+        token.error(format!("expected {} comma separated numbers, found '{}'", count, token.text))
+    };
+    let mut components = [0.0; N];
+    let mut count = 0;
+    let mut offset = 0;
+    for part in token.text.split(',') {
+        if count == N {
+            return Err(expected());
+        }
+        components[count] = parse_float(token.sub_token(offset, part))?;
+        count += 1;
+        offset += part.len() + 1;
+    }
+    if count != N {
+        return Err(expected());
+    }
+    Ok(components)
+}
+
+fn parse_seed(token: Token) -> Result<u64, SceneError> {
+    token.text.parse::<u64>()
+        .map_err(|_| token.error(format!("expected a whole number, found '{}'", token.text)))
+}
+
+fn parse_vector(token: Token) -> Result<Point, SceneError> {
+    let [x, y, z] = parse_numbers(token)?;
+    Ok(Point::new(x, y, z))
+}
+
+struct Property<'a> {
+    key: Token<'a>,
+    value: Token<'a>,
+    used: bool,
+}
+
+// The key=value pairs of one statement. Every lookup marks the key as used so
+// `finish` can point at anything the statement doesn't understand.
+struct Properties<'a> {
+    keyword: Token<'a>,
+    entries: Vec<Property<'a>>,
+}
+
+impl<'a> Properties<'a> {
+    fn new(keyword: Token<'a>, tokens: &[Token<'a>]) -> Result<Properties<'a>, SceneError> {
+        let mut entries: Vec<Property<'a>> = Vec::new();
+        for &token in tokens {
+            let (key, value) = match token.text.split_once('=') {
+                Some((key, value)) if !key.is_empty() => (key, value),
+                _ => return Err(token.error(format!("expected key=value, found '{}'", token.text))),
+            };
+            let key = token.sub_token(0, key);
+            let value = token.sub_token(key.text.len() + 1, value);
+            if value.text.is_empty() {
+                return Err(value.error(format!("missing value for '{}'", key.text)));
+            }
+            if entries.iter().any(|e| e.key.text == key.text) {
+                return Err(key.error(format!("'{}' is given more than once", key.text)));
+            }
+            entries.push(Property { key, value, used: false });
+        }
+        Ok(Properties { keyword, entries })
+    }
+
+    fn take(&mut self, key: &str) -> Option<Token<'a>> {
+        let entry = self.entries.iter_mut().find(|e| e.key.text == key)?;
+        entry.used = true;
+        Some(entry.value)
+    }
+
+    fn positive_float(&mut self, key: &str) -> Result<Option<f32>, SceneError> {
+        match self.take(key) {
+            Some(token) => {
+                let value = parse_float(token)?;
+                if value <= 0.0 {
+                    return Err(token.error(format!("'{}' must be greater than zero", key)));
+                }
+                Ok(Some(value))
+            }
+            None => Ok(None),
+        }
+    }
+
+    fn positive_int(&mut self, key: &str) -> Result<Option<i32>, SceneError> {
+        match self.take(key) {
+            Some(token) => match token.text.parse::<i32>() {
+                Ok(value) if value > 0 => Ok(Some(value)),
+                _ => Err(token.error(format!("'{}' must be a whole number greater than zero", key))),
+            },
+            None => Ok(None),
+        }
+    }
+
+    fn vector(&mut self, key: &str) -> Result<Option<Point>, SceneError> {
+        self.take(key).map(parse_vector).transpose()
+    }
+
+    fn float(&mut self, key: &str) -> Result<Option<f32>, SceneError> {
+        self.take(key).map(parse_float).transpose()
+    }
+
+    // "low,high" with low below high.
+    fn range(&mut self, key: &str) -> Result<Option<(f32, f32)>, SceneError> {
+        match self.take(key) {
+            Some(token) => {
+                let [low, high] = parse_numbers(token)?;
+                if low >= high {
+                    return Err(token.error(format!("'{}' must go from low to high, found '{}'", key, token.text)));
+                }
+                Ok(Some((low, high)))
+            }
+            None => Ok(None),
+        }
+    }
+
+    fn required<T>(&self, key: &str, value: Option<T>) -> Result<T, SceneError> {
+        value.ok_or_else(|| self.keyword.error(format!("'{}' needs '{}'", self.keyword.text, key)))
+    }
+
+    fn finish(self) -> Result<(), SceneError> {
+        match self.entries.iter().find(|e| !e.used) {
+            Some(e) => Err(e.key.error(format!("unknown property '{}' for '{}'", e.key.text, self.keyword.text))),
+            None => Ok(()),
+        }
+    }
+}
+
+struct Parser<'a> {
+    base_dir: PathBuf,
+    world: HittableList,
+    textures: HashMap<&'a str, Arc<dyn Texture>>,
+    materials: HashMap<&'a str, Arc<dyn Material>>,
+    meshes: HashMap<(PathBuf, &'a str), Arc<Mesh>>,
+    seen: HashMap<&'static str, usize>,
+
+    lookfrom: Point,
+    lookat: Point,
+    vup: Point,
+    vfov: f32,
+    aperture: f32,
+    focus_dist: f32,
+    shutter: (f32, f32),
+
+    sky: Sky,
+    settings: RenderSettings,
+}
+
+impl<'a> Parser<'a> {
+    fn new(base_dir: PathBuf) -> Parser<'a> {
+        Parser {
+            base_dir,
+            world: HittableList::new(),
+            textures: HashMap::new(),
+            materials: HashMap::new(),
+            meshes: HashMap::new(),
+            seen: HashMap::new(),
+            lookfrom: Point::new(0.0, 2.0, 3.0),
+            lookat: Point::new(0.0, 0.0, 0.0),
+            vup: Point::new(0.0, 1.0, 0.0),
+            vfov: 90.0,
+            aperture: 0.1,
+            focus_dist: 10.0,
+            shutter: (0.0, 0.0),
+            sky: Sky::default(),
+            settings: RenderSettings::default(),
+        }
+    }
+
+    fn statement(&mut self, keyword: Token<'a>, rest: &[Token<'a>]) -> Result<(), SceneError> {
+        match keyword.text {
+            "camera" => {
+                self.once("camera", keyword)?;
+                self.camera(Properties::new(keyword, rest)?)
+            }
+            "render" => {
+                self.once("render", keyword)?;
+                self.render(Properties::new(keyword, rest)?)
+            }
+            "sky" => {
+                self.once("sky", keyword)?;
+                if let [switch] = rest {
+                    if switch.text == "off" {
+                        self.sky = Sky::black();
+                        return Ok(());
+                    }
+                }
+                self.sky(Properties::new(keyword, rest)?)
+            }
+            "texture" => self.texture(keyword, rest),
+            "material" => self.material(keyword, rest),
+            "sphere" => self.sphere(Properties::new(keyword, rest)?),
+            "moving_sphere" => self.moving_sphere(Properties::new(keyword, rest)?),
+            "xy_rect" | "xz_rect" | "yz_rect" => self.rect(Properties::new(keyword, rest)?),
+            "plane" => self.plane(Properties::new(keyword, rest)?),
+            "box" => self.cuboid(Properties::new(keyword, rest)?),
+            "mesh" => self.mesh(Properties::new(keyword, rest)?),
+            _ => Err(keyword.error(format!("unknown statement '{}'", keyword.text))),
+        }
+    }
+
+    fn once(&mut self, name: &'static str, keyword: Token) -> Result<(), SceneError> {
+        if let Some(line) = self.seen.insert(name, keyword.line) {
+            return Err(keyword.error(format!("'{}' was already given on line {}", name, line)));
+        }
+        Ok(())
+    }
+
+    fn camera(&mut self, mut props: Properties<'a>) -> Result<(), SceneError> {
+        self.lookfrom = props.vector("lookfrom")?.unwrap_or(self.lookfrom);
+        self.lookat = props.vector("lookat")?.unwrap_or(self.lookat);
+        let vup = props.take("vup");
+        self.vup = vup.map(parse_vector).transpose()?.unwrap_or(self.vup);
+        if let Some(token) = props.take("vfov") {
+            self.vfov = parse_float(token)?;
+            if self.vfov <= 0.0 || self.vfov >= 180.0 {
+                return Err(token.error("'vfov' must be between 0 and 180 degrees"));
+            }
+        }
+        if let Some(token) = props.take("aperture") {
+            self.aperture = parse_float(token)?;
+            if self.aperture < 0.0 {
+                return Err(token.error("'aperture' can't be negative"));
+            }
+        }
+        self.focus_dist = props.positive_float("focus_dist")?.unwrap_or(self.focus_dist);
+        self.shutter = props.range("shutter")?.unwrap_or(self.shutter);
+        if (self.lookfrom - self.lookat).near_zero() {
+            return Err(props.keyword.error("'lookfrom' and 'lookat' must be different points"));
+        }
+        // The camera can't tell which way is up if vup is zero or lies along
+        // the line of sight.
+        if cross(self.vup, self.lookfrom - self.lookat).near_zero() {
+            return Err(vup.unwrap_or(props.keyword).error("'vup' can't be zero or parallel to the view direction"));
+        }
+        props.finish()
+    }
+
+    fn render(&mut self, mut props: Properties<'a>) -> Result<(), SceneError> {
+        self.settings.width = props.positive_int("width")?.map_or(self.settings.width, |w| w as u32);
+        self.settings.height = props.positive_int("height")?.map_or(self.settings.height, |h| h as u32);
+        self.settings.samples_per_pixel = props.positive_int("samples")?.unwrap_or(self.settings.samples_per_pixel);
+        self.settings.depth = props.positive_int("depth")?.unwrap_or(self.settings.depth);
+        if let Some(token) = props.take("tonemap") {
+            self.settings.tonemap.operator = Operator::from_name(token.text)
+                .ok_or_else(|| token.error(format!("unknown tonemap '{}', expected clamp, reinhard or aces", token.text)))?;
+        }
+        if let Some(token) = props.take("exposure") {
+            self.settings.tonemap.exposure = parse_float(token)?;
+        }
+        if let Some(token) = props.take("seed") {
+            self.settings.seed = parse_seed(token)?;
+        }
+        if let Some(token) = props.take("sampler") {
+            self.settings.sampler = SamplerKind::from_name(token.text)
+                .ok_or_else(|| token.error(format!("unknown sampler '{}', expected independent, stratified, halton or sobol", token.text)))?;
+        }
+        if let Some(error) = props.positive_float("adaptive")? {
+            self.settings.adaptive = Some(error);
+        }
+        props.finish()
+    }
+
+    fn sky(&mut self, mut props: Properties<'a>) -> Result<(), SceneError> {
+        if let Some(color) = props.vector("color")? {
+            if let Some(e) = props.entries.iter().find(|e| e.key.text == "horizon" || e.key.text == "zenith") {
+                return Err(e.key.error(format!("'{}' can't be combined with 'color'", e.key.text)));
+            }
+            self.sky = Sky::new(color, color);
+        }
+        self.sky.horizon = props.vector("horizon")?.unwrap_or(self.sky.horizon);
+        self.sky.zenith = props.vector("zenith")?.unwrap_or(self.sky.zenith);
+        props.finish()
+    }
+
+    // The `<name> <type>` that starts texture and material statements.
+    fn name_and_kind(keyword: Token<'a>, rest: &[Token<'a>]) -> Result<(Token<'a>, Token<'a>), SceneError> {
+        let name = match rest.first() {
+            Some(name) if !name.text.contains('=') => *name,
+            Some(token) => return Err(token.error(format!("expected a {} name", keyword.text))),
+            None => return Err(keyword.end().error(format!("expected a {} name", keyword.text))),
+        };
+        let kind = match rest.get(1) {
+            Some(kind) if !kind.text.contains('=') => *kind,
+            Some(token) => return Err(token.error(format!("expected a {} type", keyword.text))),
+            None => return Err(name.end().error(format!("expected a {} type", keyword.text))),
+        };
+        Ok((name, kind))
+    }
+
+    fn texture(&mut self, keyword: Token<'a>, rest: &[Token<'a>]) -> Result<(), SceneError> {
+        let (name, kind) = Parser::name_and_kind(keyword, rest)?;
+        if self.textures.contains_key(name.text) {
+            return Err(name.error(format!("texture '{}' is already defined", name.text)));
+        }
+
+        let mut props = Properties::new(kind, &rest[2..])?;
+        let texture: Arc<dyn Texture> = match kind.text {
+            "solid" => {
+                let color = props.vector("color")?;
+                Arc::new(SolidColor::new(props.required("color", color)?))
+            }
+            "checker" => {
+                let even = self.color(&mut props, "even")?;
+                let odd = self.color(&mut props, "odd")?;
+                let size = props.positive_float("size")?.unwrap_or(1.0);
+                Arc::new(Checker::new(props.required("even", even)?, props.required("odd", odd)?, size))
+            }
+            "image" => {
+                let file = props.take("file");
+                let file = props.required("file", file)?;
+                let wrap = match props.take("wrap") {
+                    Some(token) => Wrap::from_name(token.text)
+                        .ok_or_else(|| token.error(format!("unknown wrap '{}', expected repeat or clamp", token.text)))?,
+                    None => Wrap::Repeat,
+                };
+                let path = self.base_dir.join(file.text);
+                let texture = ImageTexture::read_file(&path, wrap)
+                    .map_err(|err| file.error(format!("can't load '{}': {}", path.display(), err)))?;
+                Arc::new(texture)
+            }
+            "noise" | "marble" | "wood" => {
+                let pattern = Pattern::from_name(kind.text).expect("matched above");
+                let scale = props.positive_float("scale")?.unwrap_or(1.0);
+                let seed = props.take("seed").map(parse_seed).transpose()?.unwrap_or(0);
+                let low = self.color(&mut props, "low")?.unwrap_or_else(|| Arc::new(SolidColor::new(Point::new(0.0, 0.0, 0.0))));
+                let high = self.color(&mut props, "high")?.unwrap_or_else(|| Arc::new(SolidColor::new(Point::new(1.0, 1.0, 1.0))));
+                Arc::new(NoiseTexture::new(pattern, seed, scale, low, high))
+            }
+            _ => return Err(kind.error(format!("unknown texture type '{}'", kind.text))),
+        };
+        props.finish()?;
+        self.textures.insert(name.text, texture);
+        Ok(())
+    }
+
+    // A <color> property: either r,g,b or the name of a texture.
+    fn color(&self, props: &mut Properties<'a>, key: &str) -> Result<Option<Arc<dyn Texture>>, SceneError> {
+        let token = match props.take(key) {
+            Some(token) => token,
+            None => return Ok(None),
+        };
+        if token.text.contains(',') {
+            return Ok(Some(Arc::new(SolidColor::new(parse_vector(token)?))));
+        }
+        self.textures.get(token.text)
+            .cloned()
+            .map(Some)
+            .ok_or_else(|| token.error(format!("unknown texture '{}'", token.text)))
+    }
+
+    fn material(&mut self, keyword: Token<'a>, rest: &[Token<'a>]) -> Result<(), SceneError> {
+        let (name, kind) = Parser::name_and_kind(keyword, rest)?;
+        if self.materials.contains_key(name.text) {
+            return Err(name.error(format!("material '{}' is already defined", name.text)));
+        }
+
+        let mut props = Properties::new(kind, &rest[2..])?;
+        let material: Arc<dyn Material> = match kind.text {
+            "lambertian" => {
+                let albedo = self.color(&mut props, "albedo")?;
+                Arc::new(Lambertian::textured(props.required("albedo", albedo)?))
+            }
+            "metal" => {
+                let albedo = self.color(&mut props, "albedo")?;
+                let fuzz = match props.take("fuzz") {
+                    Some(token) => {
+                        let fuzz = parse_float(token)?;
+                        if !(0.0..=1.0).contains(&fuzz) {
+                            return Err(token.error("'fuzz' must be between 0 and 1"));
+                        }
+                        fuzz
+                    }
+                    None => 0.0,
+                };
+                Arc::new(Metal::textured(props.required("albedo", albedo)?, fuzz))
+            }
+            "dielectric" => {
+                let ir = props.positive_float("ir")?;
+                Arc::new(Dielectric::new(props.required("ir", ir)?))
+            }
+            "light" => {
+                if let Some(entry) = props.entries.iter().find(|e| e.key.text == "emit" && e.value.text.contains(',')) {
+                    let emit = parse_vector(entry.value)?;
+                    if emit[0] < 0.0 || emit[1] < 0.0 || emit[2] < 0.0 {
+                        return Err(entry.value.error("'emit' can't be negative"));
+                    }
+                }
+                let emit = self.color(&mut props, "emit")?;
+                Arc::new(DiffuseLight::textured(props.required("emit", emit)?))
+            }
+            "isotropic" => {
+                let albedo = self.color(&mut props, "albedo")?;
+                Arc::new(Isotropic::textured(props.required("albedo", albedo)?))
+            }
+            _ => return Err(kind.error(format!("unknown material type '{}'", kind.text))),
+        };
+        props.finish()?;
+        self.materials.insert(name.text, material);
+        Ok(())
+    }
+
+    fn lookup_material(&self, props: &mut Properties<'a>) -> Result<Arc<dyn Material>, SceneError> {
+        let token = props.take("material");
+        let token = props.required("material", token)?;
+        self.materials.get(token.text)
+            .cloned()
+            .ok_or_else(|| token.error(format!("unknown material '{}'", token.text)))
+    }
+
+    fn sphere(&mut self, mut props: Properties<'a>) -> Result<(), SceneError> {
+        let center = props.vector("center")?;
+        let center = props.required("center", center)?;
+        let radius = props.positive_float("radius")?;
+        let radius = props.required("radius", radius)?;
+        let material = self.lookup_material(&mut props)?;
+        self.place(props, Sphere::new(center, radius, material))
+    }
+
+    fn moving_sphere(&mut self, mut props: Properties<'a>) -> Result<(), SceneError> {
+        let center0 = props.vector("center0")?;
+        let center0 = props.required("center0", center0)?;
+        let center1 = props.vector("center1")?;
+        let center1 = props.required("center1", center1)?;
+        let (time0, time1) = props.range("time")?.unwrap_or((0.0, 1.0));
+        let radius = props.positive_float("radius")?;
+        let radius = props.required("radius", radius)?;
+        let material = self.lookup_material(&mut props)?;
+        self.place(props, MovingSphere::new(center0, center1, time0, time1, radius, material))
+    }
+
+    fn rect(&mut self, mut props: Properties<'a>) -> Result<(), SceneError> {
+        let keyword = props.keyword.text;
+        let (a_key, b_key, k_key) = match keyword {
+            "xy_rect" => ("x", "y", "z"),
+            "xz_rect" => ("x", "z", "y"),
+            _ => ("y", "z", "x"),
+        };
+        let a = props.range(a_key)?;
+        let (a0, a1) = props.required(a_key, a)?;
+        let b = props.range(b_key)?;
+        let (b0, b1) = props.required(b_key, b)?;
+        let k = props.float(k_key)?;
+        let k = props.required(k_key, k)?;
+        let material = self.lookup_material(&mut props)?;
+        match keyword {
+            "xy_rect" => self.place(props, XYRect::new(a0, a1, b0, b1, k, material)),
+            "xz_rect" => self.place(props, XZRect::new(a0, a1, b0, b1, k, material)),
+            _ => self.place(props, YZRect::new(a0, a1, b0, b1, k, material)),
+        }
+    }
+
+    fn plane(&mut self, mut props: Properties<'a>) -> Result<(), SceneError> {
+        let point = props.vector("point")?;
+        let point = props.required("point", point)?;
+        let normal_token = props.take("normal");
+        let normal_token = props.required("normal", normal_token)?;
+        let normal = parse_vector(normal_token)?;
+        if normal.near_zero() {
+            return Err(normal_token.error("'normal' can't be zero"));
+        }
+        let material = self.lookup_material(&mut props)?;
+        self.place(props, Plane::new(point, normal, material))
+    }
+
+    fn cuboid(&mut self, mut props: Properties<'a>) -> Result<(), SceneError> {
+        let min = props.vector("min")?;
+        let min = props.required("min", min)?;
+        let max_token = props.take("max");
+        let max_token = props.required("max", max_token)?;
+        let max = parse_vector(max_token)?;
+        if min.x >= max.x || min.y >= max.y || min.z >= max.z {
+            return Err(max_token.error("'max' must be above 'min' on every axis"));
+        }
+        let material = self.lookup_material(&mut props)?;
+        self.place(props, Cuboid::new(min, max, material))
+    }
+
+    fn mesh(&mut self, mut props: Properties<'a>) -> Result<(), SceneError> {
+        let file = props.take("file");
+        let file = props.required("file", file)?;
+        let material_name = props.entries.iter().find(|e| e.key.text == "material").map_or("", |e| e.value.text);
+        let material = self.lookup_material(&mut props)?;
+
+        let path = self.base_dir.join(file.text);
+        let key = (path.clone(), material_name);
+        let mesh = match self.meshes.get(&key) {
+            Some(mesh) => mesh.clone(),
+            None => {
+                let mesh = Arc::new(Mesh::load_obj(&path, material)
+                    .map_err(|err| file.error(format!("can't load '{}': {}", path.display(), err)))?);
+                self.meshes.insert(key, mesh.clone());
+                mesh
+            }
+        };
+        self.place(props, mesh)
+    }
+
+    // Adds `object` to the world, wrapped in a Transform and then a
+    // ConstantMedium if the statement asks for them.
+    fn place<H: Hittable + 'static>(&mut self, mut props: Properties<'a>, object: H) -> Result<(), SceneError> {
+        let keyword = props.keyword;
+        let mut to_world = Matrix4::identity();
+        let mut transformed = false;
+        let scale = props.take("scale");
+        if let Some(token) = scale {
+            let factors = if token.text.contains(',') {
+                parse_vector(token)?
+            } else {
+                let factor = parse_float(token)?;
+                Point::new(factor, factor, factor)
+            };
+            to_world = Matrix4::scaling(factors);
+            transformed = true;
+        }
+        if let Some(degrees) = props.vector("rotate")? {
+            for axis in 0..3 {
+                if degrees[axis] != 0.0 {
+                    to_world = Matrix4::rotation(axis, degrees[axis]) * to_world;
+                }
+            }
+            transformed = true;
+        }
+        if let Some(offset) = props.vector("translate")? {
+            to_world = Matrix4::translation(offset) * to_world;
+            transformed = true;
+        }
+        let density = props.positive_float("density")?;
+        let material_name = props.entries.iter().find(|e| e.key.text == "material").map_or("", |e| e.value.text);
+        props.finish()?;
+        // Only a scale can leave the transform with no inverse.
+        let instance = |object: H| {
+            Transform::new(Arc::new(object), to_world)
+                .ok_or_else(|| scale.unwrap_or(keyword).error("'scale' is too close to zero"))
+        };
+
+        match density {
+            Some(density) => {
+                // Every shape has already looked its material up by now.
+                let material = self.materials[material_name].clone();
+                let boundary: Arc<dyn Hittable> = if transformed {
+                    Arc::new(instance(object)?)
+                } else {
+                    Arc::new(object)
+                };
+                self.world.add(ConstantMedium::new(boundary, density, material));
+            }
+            None if transformed => self.world.add(instance(object)?),
+            None => self.world.add(object),
+        }
+        Ok(())
+    }
+
+    fn finish(self) -> Scene {
+        Scene {
+            world: self.world,
+            camera: Camera::new(self.lookfrom, self.lookat, self.vup, self.vfov, self.settings.aspect_ratio(),
+                                self.aperture, self.focus_dist)
+                .with_shutter(self.shutter.0, self.shutter.1),
+            sky: self.sky,
+            settings: self.settings,
+        }
+    }
+}
+
+#[cfg(test)]
+fn parse_error(source: &str) -> (usize, usize, String) {
+    match Scene::parse(source) {
+        Err(SceneError::Parse { line, column, message }) => (line, column, message),
+        Err(err) => panic!("unexpected error {}", err),
+        Ok(_) => panic!("expected a parse error"),
+    }
+}
+
+#[test]
+fn parses_default_scene() {
+    use crate::camera::Cast;
+    use crate::hittable_list::CheckHits;
+
+    let scene = Scene::parse(include_str!("../scenes/three_spheres.scene")).unwrap();
+    let mut sampler = SamplerKind::Independent.create(0, 1);
+    let r = scene.camera.get_ray(0.5, 0.5, sampler.as_mut());
+    assert!(scene.world.get_hits(r, 0.001, f32::INFINITY, sampler.rng()).is_some());
+    assert_eq!(scene.settings.width, 1600);
+    assert_eq!(scene.settings.height, 900);
+    assert_eq!(scene.settings.samples_per_pixel, 300);
+    assert_eq!(scene.settings.depth, 500);
+}
+
+#[test]
+fn reports_bad_number_position() {
+    let (line, column, message) = parse_error("material red lambertian albedo=1,0,0\n\nsphere center=0,1,zero radius=1 material=red");
+    assert_eq!((line, column), (3, 19));
+    assert!(message.contains("'zero'"));
+}
+
+#[test]
+fn reports_unknown_material() {
+    let (line, column, _) = parse_error("sphere center=0,0,0 radius=1 material=gold");
+    assert_eq!((line, column), (1, 39));
+}
+
+#[test]
+fn reports_unknown_property() {
+    let (line, column, message) = parse_error("  camera vfov=40 fov=30 # comment");
+    assert_eq!((line, column), (1, 18));
+    assert!(message.contains("'fov'"));
+}
+
+#[test]
+fn reports_missing_property_at_statement() {
+    let (line, column, message) = parse_error("material red lambertian albedo=1,0,0\n  sphere radius=1 material=red");
+    assert_eq!((line, column), (2, 3));
+    assert!(message.contains("'center'"));
+}
+
+#[test]
+fn rejects_negative_light() {
+    let (line, column, message) = parse_error("material lamp light emit=1,-1,1");
+    assert_eq!((line, column), (1, 26));
+    assert!(message.contains("'emit'"));
+}
+
+#[test]
+fn rejects_repeated_camera() {
+    let (line, _, message) = parse_error("camera vfov=40\ncamera vfov=50");
+    assert_eq!(line, 2);
+    assert!(message.contains("line 1"));
+}
+
+#[test]
+fn rejects_camera_without_an_up_direction() {
+    let (line, column, message) = parse_error("camera lookfrom=0,0,0 lookat=0,0,-1 vup=0,0,2");
+    assert_eq!((line, column), (1, 41));
+    assert!(message.contains("'vup'"));
+    let (line, column, _) = parse_error("\ncamera lookfrom=0,0,0 lookat=1,1,1 vup=0,0,0");
+    assert_eq!((line, column), (2, 40));
+    // Looking straight down with the default vup.
+    let (_, column, _) = parse_error("camera lookfrom=0,5,0 lookat=0,0,0");
+    assert_eq!(column, 1);
+}
+
+#[test]
+fn reads_tonemap_settings() {
+    use crate::tonemap::Tonemap;
+
+    let scene = Scene::parse("render tonemap=ACES exposure=-1.5").unwrap();
+    assert_eq!(scene.settings.tonemap, Tonemap::new(Operator::Aces, -1.5));
+    let (line, column, message) = parse_error("render tonemap=filmic");
+    assert_eq!((line, column), (1, 16));
+    assert!(message.contains("'filmic'"));
+}
+
+#[test]
+fn reads_adaptive_sampling() {
+    assert_eq!(Scene::parse("").unwrap().settings.adaptive, None);
+    assert_eq!(Scene::parse("render adaptive=0.05").unwrap().settings.adaptive, Some(0.05));
+    let (_, column, _) = parse_error("render samples=64 adaptive=0");
+    assert_eq!(column, 28);
+}
+
+#[test]
+fn reads_lights_and_background_switches() {
+    let scene = Scene::parse("sky off\nmaterial lamp light emit=4,4,4\nsphere center=0,0,0 radius=1 material=lamp").unwrap();
+    assert_eq!(scene.sky.horizon, Point::new(0.0, 0.0, 0.0));
+    assert_eq!(scene.sky.zenith, Point::new(0.0, 0.0, 0.0));
+    let scene = Scene::parse("sky color=0.1,0.2,0.3").unwrap();
+    assert_eq!(scene.sky.zenith, Point::new(0.1, 0.2, 0.3));
+    let (_, column, _) = parse_error("sky color=1,1,1 zenith=0,0,1");
+    assert_eq!(column, 17);
+    assert!(Scene::parse("material lamp light").is_err());
+}
+
+#[test]
+fn reads_rects_planes_and_boxes() {
+    use crate::hittable_list::CheckHits;
+    use crate::ray::Ray;
+
+    let scene = Scene::parse(include_str!("../scenes/cornell_box.scene")).unwrap();
+    let mut rng = crate::random::Pcg32::new(7, 0);
+    // Straight up from the middle of the floor is the light.
+    let r = Ray::new(Point::new(278.0, 1.0, 278.0), Point::new(0.0, 1.0, 0.0));
+    let rec = scene.world.get_hits(r, 0.001, f32::INFINITY, &mut rng).unwrap();
+    assert!((rec.p.y - 554.0).abs() < 1e-3);
+    assert!(rec.material.emitted(&rec)[0] > 1.0);
+
+    assert!(Scene::parse("material m lambertian albedo=1,1,1\nplane point=0,0,0 normal=0,1,0 material=m").is_ok());
+    let (_, column, message) = parse_error("material m lambertian albedo=1,1,1\nxz_rect x=1,0 z=0,1 y=0 material=m");
+    assert_eq!(column, 11);
+    assert!(message.contains("low to high"));
+    let (_, _, message) = parse_error("material m lambertian albedo=1,1,1\nyz_rect y=0,1 z=0,1,2 x=0 material=m");
+    assert!(message.contains("two comma separated"));
+    let (_, _, message) = parse_error("material m lambertian albedo=1,1,1\nbox min=0,0,0 max=1,0,1 material=m");
+    assert!(message.contains("every axis"));
+}
+
+#[test]
+fn loads_meshes_next_to_the_scene() {
+    use crate::hittable_list::CheckHits;
+    use crate::ray::Ray;
+
+    let dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("scenes");
+    let scene = Scene::load(&dir.join("glass_octahedron.scene")).unwrap();
+    let mut rng = crate::random::Pcg32::new(7, 0);
+    // Down onto the face spanning +x, +y and +z, which lies on x + y + z = 1.
+    let r = Ray::new(Point::new(0.1, 5.0, 0.1), Point::new(0.0, -1.0, 0.0));
+    let rec = scene.world.get_hits(r, 0.001, f32::INFINITY, &mut rng).unwrap();
+    assert!((rec.p.y - 0.8).abs() < 1e-4);
+
+    let (line, column, message) = parse_error("material m lambertian albedo=1,1,1\nmesh file=missing.obj material=m");
+    assert_eq!((line, column), (2, 11));
+    assert!(message.contains("missing.obj"));
+}
+
+#[test]
+fn transforms_shapes_and_shares_meshes() {
+    use crate::hittable_list::CheckHits;
+    use crate::ray::Ray;
+
+    let dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("scenes");
+    let source = "material glass dielectric ir=1.5\n\
+                  mesh file=models/octahedron.obj material=glass translate=0,0,-10\n\
+                  mesh file=models/octahedron.obj material=glass scale=2 translate=10,0,-10\n\
+                  box min=-1,-1,-1 max=1,1,1 material=glass scale=1,3,1 rotate=0,0,90 translate=-10,0,-10\n";
+    let scene = Scene::parse_in(source, &dir).unwrap();
+    let mut rng = crate::random::Pcg32::new(7, 0);
+    let down = Point::new(0.0, -1.0, 0.0);
+
+    // The octahedron's faces lie on |x| + |y| + |z| = 1, twice that when scaled.
+    let hit_height = |x: f32| scene.world.get_hits(Ray::new(Point::new(x, 10.0, -10.0), down), 0.001, f32::INFINITY, &mut crate::random::Pcg32::new(7, 0))
+        .map(|rec| rec.p.y);
+    assert!((hit_height(0.5).unwrap() - 0.5).abs() < 1e-4);
+    assert!((hit_height(10.5).unwrap() - 1.5).abs() < 1e-4);
+    // The tall box was turned on its side, so it reaches 3 along x but only 1 up.
+    assert!((hit_height(-12.5).unwrap() - 1.0).abs() < 1e-4);
+    assert!(hit_height(-13.5).is_none());
+    assert!(scene.world.get_hits(Ray::new(Point::new(5.0, 10.0, -10.0), down), 0.001, f32::INFINITY, &mut rng).is_none());
+
+    let (_, _, message) = parse_error("material m lambertian albedo=1,1,1\nsphere center=0,0,0 radius=1 material=m scale=1,0,1");
+    assert!(message.contains("'scale'"));
+    let (line, column, message) = parse_error("material m lambertian albedo=1,1,1\nsphere center=0,0,0 radius=1 material=m scale=1e-13");
+    assert_eq!((line, column), (2, 47));
+    assert!(message.contains("'scale'"));
+}
+
+#[test]
+fn reads_textures() {
+    use crate::hittable_list::CheckHits;
+    use crate::ray::Ray;
+
+    let source = "texture white solid color=1,1,1\n\
+                  texture floor checker even=white odd=0,0,0 size=2\n\
+                  material m lambertian albedo=floor\n\
+                  material lamp light emit=floor\n\
+                  xz_rect x=-10,10 z=-10,10 y=0 material=m\n\
+                  xz_rect x=-10,10 z=-10,10 y=5 material=lamp\n";
+    let scene = Scene::parse(source).unwrap();
+    let mut sampler = SamplerKind::Independent.create(0, 1);
+    let up = Point::new(0.0, 1.0, 0.0);
+    // Cells are two wide and counted in 3D, so the lamp at y = 5 is two cells up.
+    let down = Ray::new(Point::new(3.0, 1.0, 1.0), -up);
+    let rec = scene.world.get_hits(down, 0.001, f32::INFINITY, sampler.rng()).unwrap();
+    assert_eq!(rec.material.scatter(down, &rec, sampler.as_mut()).unwrap().attenuation, Point::new(0.0, 0.0, 0.0));
+    let rec = scene.world.get_hits(Ray::new(Point::new(1.0, 1.0, 1.0), up), 0.001, f32::INFINITY, sampler.rng()).unwrap();
+    assert_eq!(rec.material.emitted(&rec), Point::new(1.0, 1.0, 1.0));
+
+    let (line, column, message) = parse_error("material m lambertian albedo=wood");
+    assert_eq!((line, column), (1, 30));
+    assert!(message.contains("unknown texture 'wood'"));
+    let (_, _, message) = parse_error("texture t image file=missing.png");
+    assert!(message.contains("missing.png"));
+    let (_, _, message) = parse_error("texture t granite");
+    assert!(message.contains("unknown texture type"));
+    let (_, _, message) = parse_error("material lamp light emit=1,-1,1");
+    assert!(message.contains("negative"));
+}
+
+#[test]
+fn maps_image_textures_onto_spheres() {
+    use crate::hittable_list::CheckHits;
+    use crate::ray::Ray;
+
+    let dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("scenes");
+    let scene = Scene::load(&dir.join("textured_globe.scene")).unwrap();
+    let mut sampler = SamplerKind::Independent.create(0, 1);
+    let mut albedo = |origin: Point, direction: Point| {
+        let r = Ray::new(origin, direction);
+        let rec = scene.world.get_hits(r, 0.001, f32::INFINITY, sampler.rng()).unwrap();
+        rec.material.scatter(r, &rec, sampler.as_mut()).unwrap().attenuation
+    };
+    // Straight down onto the north pole lands in the ice cap.
+    let ice = albedo(Point::new(0.0, 5.0, 0.0), Point::new(0.0, -1.0, 0.0));
+    assert!(ice[0] > 0.8 && ice[2] > 0.9);
+    // Around the equator the land and sea bands alternate.
+    let mut colors = Vec::new();
+    for i in 0..8 {
+        let angle = (i as f32 + 0.5) * std::f32::consts::PI / 4.0;
+        let out = Point::new(angle.cos(), 0.05, angle.sin());
+        colors.push(albedo(Point::new(0.0, 1.0, 0.0) + 5.0 * out, -out));
+    }
+    assert!(colors.iter().any(|c| c[1] > c[2]) && colors.iter().any(|c| c[2] > c[1]));
+    assert!(scene.world.get_hits(Ray::new(Point::new(5.0, 5.0, 5.0), Point::new(0.0, -1.0, 0.0)), 0.001, f32::INFINITY, sampler.rng()).is_some());
+
+    let (_, column, message) = parse_error("texture t image file=textures/globe.ppm wrap=mirror");
+    assert_eq!(column, 46);
+    assert!(message.contains("unknown wrap"));
+}
+
+#[test]
+fn reads_noise_textures() {
+    let scene = Scene::parse("texture veins marble scale=4 seed=7 low=0.1,0.1,0.1 high=0.9,0.9,0.9\n\
+                              texture rings wood scale=8 low=veins high=0.8,0.6,0.3\n\
+                              material stone metal albedo=veins fuzz=0.2\n\
+                              material oak lambertian albedo=rings\n\
+                              sphere center=0,0,0 radius=1 material=stone");
+    assert!(scene.is_ok());
+    let (line, column, message) = parse_error("texture t noise seed=-1");
+    assert_eq!((line, column), (1, 22));
+    assert!(message.contains("whole number"));
+}
+
+#[test]
+fn reads_shutter_and_moving_spheres() {
+    use crate::camera::Cast;
+    use crate::hittable_list::CheckHits;
+    use crate::ray::Ray;
+
+    let scene = Scene::parse("camera shutter=0.5,1\n\
+                              material m lambertian albedo=1,1,1\n\
+                              moving_sphere center0=0,0,-5 center1=0,4,-5 radius=1 material=m\n").unwrap();
+    let mut sampler = SamplerKind::Independent.create(0, 1);
+    for _ in 0..100 {
+        let time = scene.camera.get_ray(0.5, 0.5, sampler.as_mut()).time;
+        assert!((0.5..1.0).contains(&time));
+    }
+    // The sphere passes y = 2 halfway through, and y = -1.5 is never reached.
+    let forward = Point::new(0.0, 0.0, -1.0);
+    assert!(scene.world.get_hits(Ray::with_time(Point::new(0.0, 2.0, 0.0), forward, 0.5), 0.001, f32::INFINITY, sampler.rng()).is_some());
+    assert!(scene.world.get_hits(Ray::with_time(Point::new(0.0, 2.0, 0.0), forward, 0.0), 0.001, f32::INFINITY, sampler.rng()).is_none());
+    assert!(scene.world.get_hits(Ray::with_time(Point::new(0.0, -1.5, 0.0), forward, 0.0), 0.001, f32::INFINITY, sampler.rng()).is_none());
+    assert_eq!(Scene::parse("").unwrap().camera.get_ray(0.5, 0.5, sampler.as_mut()).time, 0.0);
+
+    let (_, _, message) = parse_error("camera shutter=1,0");
+    assert!(message.contains("low to high"));
+    let (_, _, message) = parse_error("material m lambertian albedo=1,1,1\nmoving_sphere center0=0,0,0 radius=1 material=m");
+    assert!(message.contains("'center1'"));
+}
+
+#[test]
+fn fills_shapes_with_fog() {
+    use crate::hittable_list::CheckHits;
+    use crate::ray::Ray;
+
+    let scene = Scene::parse("material smoke isotropic albedo=0.5,0.5,0.5\n\
+                              box min=-1,-1,-1 max=1,1,1 material=smoke density=100 translate=0,0,-5\n").unwrap();
+    let mut sampler = SamplerKind::Independent.create(0, 1);
+    // Thick enough that rays stop just inside the front face, and scatter anywhere.
+    let r = Ray::new(Point::default(), Point::new(0.0, 0.0, -1.0));
+    let rec = scene.world.get_hits(r, 0.001, f32::INFINITY, sampler.rng()).unwrap();
+    assert!(rec.p.z < -4.0 && rec.p.z > -4.5);
+    let scattered = rec.material.scatter(r, &rec, sampler.as_mut()).unwrap();
+    assert!((scattered.ray_dir.direction.length() - 1.0).abs() < 1e-4);
+    assert_eq!(scattered.attenuation, Point::new(0.5, 0.5, 0.5));
+
+    let (_, _, message) = parse_error("material smoke isotropic albedo=1,1,1\nsphere center=0,0,0 radius=1 material=smoke density=0");
+    assert!(message.contains("greater than zero"));
+}
@@ -0,0 +1,51 @@
+use crate::{Point, Ray};
+use crate::utility::unit_vector;
+
+// Background seen by rays that leave the scene.
+#[derive(Clone, Copy, Debug)]
+pub struct Sky {
+    pub horizon: Point,
+    pub zenith: Point,
+}
+
+impl Sky {
+    pub fn new(horizon: Point, zenith: Point) -> Sky {
+        Sky {
+            horizon,
+            zenith,
+        }
+    }
+
+    // A background that adds no light, for scenes lit only by emitters.
+    pub fn black() -> Sky {
+        Sky::new(Point::new(0.0, 0.0, 0.0), Point::new(0.0, 0.0, 0.0))
+    }
+
+    pub fn color(&self, r: Ray) -> Point {
+        let unit_direction = unit_vector(r.direction);
+        // 0 looking straight down, 1 straight up, so the blend never leaves
+        // the two colors.
+        let t = 0.5 * (unit_direction.y + 1.0);
+        (1.0 - t) * self.horizon + t * self.zenith
+    }
+}
+
+impl Default for Sky {
+    fn default() -> Self {
+        Sky::new(Point::new(0.9, 0.9, 0.9), Point::new(0.5, 0.7, 1.0))
+    }
+}
+
+#[cfg(test)]
+#[test]
+fn blends_between_horizon_and_zenith() {
+    let sky = Sky::default();
+    assert_eq!(sky.color(Ray::new(Point::new(0.0, 0.0, 0.0), Point::new(0.0, 3.0, 0.0))), sky.zenith);
+    for i in 0..=20 {
+        let y = i as f32 / 10.0 - 1.0;
+        let color = sky.color(Ray::new(Point::new(0.0, 0.0, 0.0), Point::new(1.0 - y.abs(), y, 0.0)));
+        for channel in 0..3 {
+            assert!(color[channel] >= 0.0 && color[channel] <= 1.0, "{:?}", color);
+        }
+    }
+}
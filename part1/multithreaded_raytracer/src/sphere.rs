@@ -1,36 +1,46 @@
-use num::pow;
-use rand::prelude::ThreadRng;
-use rand::Rng;
-use crate::{Point, random_unit_vector, Ray};
+use std::f32::consts::PI;
+use std::sync::Arc;
+
+use rand::RngCore;
+
+use crate::{Point, Ray};
 use crate::aabb::AABB;
 use crate::hittable::{HitRecord, Hittable};
-use crate::scatter_results::ScatterResults;
-use crate::utility::{dot, random_in_unit_sphere, reflect, refract, unit_vector};
+use crate::material::Material;
+use crate::utility::dot;
 
-#[derive(Clone, Copy)]
+#[derive(Clone)]
 pub struct Sphere {
     pub center: Point,
     pub radius: f32,
+    pub material: Arc<dyn Material>,
 }
 
 impl Sphere {
-    pub fn new(center: Point, radius: f32) -> Sphere {
+    pub fn new(center: Point, radius: f32, material: Arc<dyn Material>) -> Sphere {
         Sphere {
             center,
             radius,
+            material,
         }
     }
 }
 
+// Longitude and latitude of a point on the unit sphere, both scaled to 0..1.
+// u starts at -x and goes around through +z, v runs from the -y pole to +y.
+pub fn sphere_uv(p: Point) -> (f32, f32) {
+    let theta = (-p.y).clamp(-1.0, 1.0).acos();
+    let phi = (-p.z).atan2(p.x) + PI;
+    (phi / (2.0 * PI), theta / PI)
+}
 
-fn hit_sphere<T: Into<Sphere> + Hittable + Clone>(sphere: T, r: Ray, t_min: f32, t_max: f32, rng: &mut ThreadRng) -> Option<HitRecord>
-    where Sphere: From<T>
-{
-    let s: Sphere = sphere.clone().into();
-    let oc = r.origin - s.center;
+// Shared by Sphere and MovingSphere, which only differ in where the center is.
+pub(crate) fn hit_sphere<'a>(center: Point, radius: f32, material: &'a dyn Material,
+                             r: Ray, t_min: f32, t_max: f32) -> Option<HitRecord<'a>> {
+    let oc = r.origin - center;
     let a = r.direction.length_squared();
     let half_b = dot(oc, r.direction);
-    let c = oc.length_squared() - s.radius * s.radius;
+    let c = oc.length_squared() - radius * radius;
 
     let discriminant = half_b * half_b - a * c;
     if discriminant < 0.0 {
@@ -49,201 +59,45 @@ fn hit_sphere<T: Into<Sphere> + Hittable + Clone>(sphere: T, r: Ray, t_min: f32,
 
     let t = root;
     let p = r.at(root);
-    let outward_normal: Point = (p - s.center) / s.radius;
-    let temp_normal = Point::default();
+    let outward_normal: Point = (p - center) / radius;
+    let (u, v) = sphere_uv(outward_normal);
 
     let mut rec = HitRecord {
         p,
-        normal: temp_normal,
+        normal: Point::default(),
         t,
         front_face: true,
-        scatter_results: None,
+        u,
+        v,
+        barycentric: (0.0, 0.0),
+        material,
     };
-    rec.scatter_results = sphere.scatter(r, &rec, rng);
     rec.set_face_normal(r, outward_normal);
     Some(rec)
 }
 
-fn sphere_to_bounding_box<T: Into<Sphere>>(sphere: T) -> Option<AABB> {
-    let sphere = sphere.into();
-    Some(AABB {
-        min: sphere.center - Point::new(sphere.radius, sphere.radius, sphere.radius),
-        max: sphere.center + Point::new(sphere.radius, sphere.radius, sphere.radius),
-    })
-}
-
-impl From<LambertianSphere> for Sphere {
-    fn from(lam: LambertianSphere) -> Self {
-        Sphere {
-            center: lam.center,
-            radius: lam.radius,
-        }
-    }
-}
-
-impl From<MetalSphere> for Sphere {
-    fn from(metal: MetalSphere) -> Self {
-        Sphere {
-            center: metal.center,
-            radius: metal.radius,
-        }
-    }
-}
-
-impl From<DielectricSphere> for Sphere {
-    fn from(glass: DielectricSphere) -> Self {
-        Sphere {
-            center: glass.center,
-            radius: glass.radius,
-        }
-    }
-}
-
-#[derive(Clone, Copy)]
-pub struct LambertianSphere {
-    pub center: Point,
-    pub radius: f32,
-    pub albedo: Point,
-}
-
-impl Hittable for LambertianSphere {
-    fn hit(&self, r: Ray, t_min: f32, t_max: f32, rng: &mut ThreadRng) -> Option<HitRecord> {
-        hit_sphere(self.clone(), r, t_min, t_max, rng)
-    }
-
-    fn scatter(&self, _r_in: Ray, rec: &HitRecord, rng: &mut ThreadRng) -> Option<ScatterResults> {
-        let mut scatter_direction = rec.normal + random_unit_vector(rng);
-        if scatter_direction.near_zero() { scatter_direction = rec.normal; }
-        let scattered = Ray::new(rec.p, scatter_direction);
-        let attenuation = self.albedo;
-        Some(
-            ScatterResults {
-                ray_dir: scattered,
-                norm: rec.normal ,// ??
-                attenuation: self.albedo
-            }
-        )
+impl Hittable for Sphere {
+    // Scattering is left to the caller so it only happens for the closest hit.
+    fn hit(&self, r: Ray, t_min: f32, t_max: f32, _rng: &mut dyn RngCore) -> Option<HitRecord<'_>> {
+        hit_sphere(self.center, self.radius, self.material.as_ref(), r, t_min, t_max)
     }
 
     fn get_bounding_box(&self) -> Option<AABB> { // Bounding Volume Requirement
-        sphere_to_bounding_box(self.clone())
-    }
-}
-
-impl LambertianSphere {
-    pub fn new(center: Point, radius: f32, albedo: Point) -> LambertianSphere {
-        LambertianSphere {
-            center,
-            radius,
-            albedo,
-        }
+        let extent = Point::new(self.radius, self.radius, self.radius);
+        Some(AABB::new(self.center - extent, self.center + extent))
     }
 }
 
-#[derive(Clone, Copy)]
-pub struct MetalSphere {
-    pub center: Point,
-    pub radius: f32,
-    pub albedo: Point,
-    fuzz: f32,
+#[cfg(test)]
+#[test]
+fn uv_follows_latitude_and_longitude() {
+    let close = |(u, v): (f32, f32), (eu, ev): (f32, f32)| (u - eu).abs() < 1e-5 && (v - ev).abs() < 1e-5;
+    assert!(close(sphere_uv(Point::new(1.0, 0.0, 0.0)), (0.5, 0.5)));
+    assert!(close(sphere_uv(Point::new(0.0, 1.0, 0.0)), (0.5, 1.0)));
+    assert!(close(sphere_uv(Point::new(0.0, -1.0, 0.0)), (0.5, 0.0)));
+    // The seam at -x can land on either end.
+    let (u, v) = sphere_uv(Point::new(-1.0, 0.0, 0.0));
+    assert!(u.min(1.0 - u) < 1e-5 && (v - 0.5).abs() < 1e-5);
+    assert!(close(sphere_uv(Point::new(0.0, 0.0, 1.0)), (0.25, 0.5)));
+    assert!(close(sphere_uv(Point::new(0.0, 0.0, -1.0)), (0.75, 0.5)));
 }
-
-impl MetalSphere {
-    pub fn new(center: Point, radius: f32, albedo: Point, fuzz: f32) -> MetalSphere {
-        MetalSphere {
-            center,
-            radius,
-            albedo,
-            fuzz,
-        }
-    }
-}
-
-impl Hittable for MetalSphere {
-    fn hit(&self, r: Ray, t_min: f32, t_max: f32, rng: &mut ThreadRng) -> Option<HitRecord> {
-        hit_sphere(self.clone(), r, t_min, t_max, rng)
-    }
-
-    fn scatter(&self, r_in: Ray, rec: &HitRecord, rng: &mut ThreadRng) -> Option<ScatterResults> {
-        let reflected = reflect(unit_vector(r_in.direction), rec.normal);
-        // Reflection Requirement
-        let scattered = Ray::new(rec.p, reflected + self.fuzz * random_in_unit_sphere(rng));
-        let attenuation = self.albedo;
-        if dot(scattered.direction, rec.normal) > 0.0 {
-            return Some(
-                ScatterResults{
-                    ray_dir: scattered,
-                    norm: rec.normal, //?
-                    attenuation
-                }
-            );
-        }
-       return None;
-    }
-
-    fn get_bounding_box(&self) -> Option<AABB> {
-        sphere_to_bounding_box(self.clone())
-    }
-}
-
-#[derive(Clone, Copy)]
-pub struct DielectricSphere {
-    pub center: Point,
-    pub radius: f32,
-    ir: f32,
-}
-
-impl DielectricSphere {
-    pub fn new(center: Point, radius: f32, ir: f32) -> DielectricSphere {
-        DielectricSphere {
-            center,
-            radius,
-            ir,
-        }
-    }
-
-    fn reflectance(cosine: f32, ref_idx: f32) -> f32 {
-        let mut r0 = (1.0 - ref_idx) / (1.0 + ref_idx);
-        r0 = r0 * r0;
-        r0 + (1.0 - r0) * pow((1.0 - cosine), 5)
-    }
-}
-
-impl Hittable for DielectricSphere {
-    fn hit(&self, r: Ray, t_min: f32, t_max: f32, rng: &mut ThreadRng) -> Option<HitRecord> {
-        hit_sphere(self.clone(), r, t_min, t_max, rng)
-    }
-
-    fn scatter(&self, r_in: Ray, rec: &HitRecord, rng: &mut ThreadRng) -> Option<ScatterResults> {
-        let refraction_ratio = if rec.front_face { 1.0 / self.ir } else { self.ir };
-        let attenuation = Point::new(1.0, 1.0, 1.0);
-        let unit_direction = unit_vector(r_in.direction);
-        let dot = dot(-unit_direction, rec.normal);
-        let cos_theta = if dot < 1.0 { dot } else { 1.0 };
-
-        let sin_theta = (1.0 - cos_theta * cos_theta).sqrt();
-        let cannot_refract = refraction_ratio * sin_theta > 1.0;
-        let rand_f32 = rng.gen_range(0.0..1.0);
-        let reflectance_bool = DielectricSphere::reflectance(cos_theta, refraction_ratio) > rand_f32;
-
-        let direction = if cannot_refract || reflectance_bool {
-            reflect(unit_direction, rec.normal)
-        } else {
-            refract(unit_direction, rec.normal, refraction_ratio)
-        };
-
-        let scattered = Ray { origin: rec.p, direction };
-        Some(
-            ScatterResults{
-                ray_dir: scattered,
-                norm: direction,
-                attenuation
-            }
-        )
-    }
-
-    fn get_bounding_box(&self) -> Option<AABB> {
-        sphere_to_bounding_box(self.clone())
-    }
-}
-
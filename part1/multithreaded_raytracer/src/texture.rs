@@ -0,0 +1,292 @@
+use std::fs;
+use std::io;
+use std::path::Path;
+use std::sync::Arc;
+
+use crate::image::Image;
+use crate::perlin::Perlin;
+use crate::png::PNG;
+use crate::point::Point;
+use crate::ppm::PPM;
+use crate::tonemap::srgb_decode;
+
+// Color that varies over a surface, looked up by the hit's surface coordinates
+// (u, v) and its position p.
+pub trait Texture: Send + Sync {
+    fn value(&self, u: f32, v: f32, p: Point) -> Point;
+}
+
+pub struct SolidColor {
+    pub color: Point,
+}
+
+impl SolidColor {
+    pub fn new(color: Point) -> SolidColor {
+        SolidColor {
+            color,
+        }
+    }
+}
+
+impl Texture for SolidColor {
+    fn value(&self, _u: f32, _v: f32, _p: Point) -> Point {
+        self.color
+    }
+}
+
+// Alternates two textures over cubes of side `size` in world space, so it
+// works the same on every shape.
+pub struct Checker {
+    pub even: Arc<dyn Texture>,
+    pub odd: Arc<dyn Texture>,
+    pub size: f32,
+}
+
+impl Checker {
+    pub fn new(even: Arc<dyn Texture>, odd: Arc<dyn Texture>, size: f32) -> Checker {
+        Checker {
+            even,
+            odd,
+            size,
+        }
+    }
+}
+
+impl Texture for Checker {
+    fn value(&self, u: f32, v: f32, p: Point) -> Point {
+        let cell = (p.x / self.size).floor() + (p.y / self.size).floor() + (p.z / self.size).floor();
+        if cell.rem_euclid(2.0) == 0.0 {
+            self.even.value(u, v, p)
+        } else {
+            self.odd.value(u, v, p)
+        }
+    }
+}
+
+// How a NoiseTexture turns Perlin noise into a blend between its two colors.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Pattern {
+    // Plain smooth noise.
+    Noise,
+    // Stripes along z, bent by turbulence.
+    Marble,
+    // Rings around the y axis, roughened by turbulence.
+    Wood,
+}
+
+impl Pattern {
+    pub fn from_name(name: &str) -> Option<Pattern> {
+        match name.to_ascii_lowercase().as_str() {
+            "noise" => Some(Pattern::Noise),
+            "marble" => Some(Pattern::Marble),
+            "wood" => Some(Pattern::Wood),
+            _ => None,
+        }
+    }
+}
+
+// Procedural solid texture, so it needs no uv and has no seams. `scale` is the
+// feature frequency: doubling it halves the size of the veins or rings.
+pub struct NoiseTexture {
+    pub pattern: Pattern,
+    pub perlin: Perlin,
+    pub scale: f32,
+    pub low: Arc<dyn Texture>,
+    pub high: Arc<dyn Texture>,
+}
+
+impl NoiseTexture {
+    pub fn new(pattern: Pattern, seed: u64, scale: f32, low: Arc<dyn Texture>, high: Arc<dyn Texture>) -> NoiseTexture {
+        NoiseTexture {
+            pattern,
+            perlin: Perlin::new(seed),
+            scale,
+            low,
+            high,
+        }
+    }
+
+    // How far towards `high` the color at `p` is, in 0..1.
+    pub fn amount(&self, p: Point) -> f32 {
+        let t = match self.pattern {
+            Pattern::Noise => 0.5 * (1.0 + self.perlin.noise(self.scale * p)),
+            Pattern::Marble => 0.5 * (1.0 + (self.scale * p.z + 10.0 * self.perlin.turbulence(p, 7)).sin()),
+            Pattern::Wood => {
+                let rings = self.scale * (p.x * p.x + p.z * p.z).sqrt() + 2.0 * self.perlin.turbulence(p, 4);
+                rings.rem_euclid(1.0)
+            }
+        };
+        t.clamp(0.0, 1.0)
+    }
+}
+
+impl Texture for NoiseTexture {
+    fn value(&self, u: f32, v: f32, p: Point) -> Point {
+        let t = self.amount(p);
+        (1.0 - t) * self.low.value(u, v, p) + t * self.high.value(u, v, p)
+    }
+}
+
+// What an image texture does with u and v outside 0..1.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Wrap {
+    // Tiles the image.
+    Repeat,
+    // Stretches the edge pixels outwards.
+    Clamp,
+}
+
+impl Wrap {
+    pub fn from_name(name: &str) -> Option<Wrap> {
+        match name.to_ascii_lowercase().as_str() {
+            "repeat" => Some(Wrap::Repeat),
+            "clamp" => Some(Wrap::Clamp),
+            _ => None,
+        }
+    }
+
+    fn index(self, i: i64, size: usize) -> usize {
+        match self {
+            Wrap::Repeat => i.rem_euclid(size as i64) as usize,
+            Wrap::Clamp => i.clamp(0, size as i64 - 1) as usize,
+        }
+    }
+}
+
+// Maps an image over u and v in 0..1, with v = 0 at the bottom row. Pixels are
+// kept as linear floats and filtered bilinearly between their centers.
+pub struct ImageTexture {
+    pub width: usize,
+    pub height: usize,
+    // Rows top to bottom, like `Image`.
+    pub pixels: Vec<Point>,
+    pub wrap: Wrap,
+}
+
+impl ImageTexture {
+    // Takes the 8-bit image as sRGB encoded.
+    pub fn new(image: &Image, wrap: Wrap) -> ImageTexture {
+        let decode = |c: &[u8]| Point::new(srgb_decode(c[0] as f32 / 255.0),
+                                           srgb_decode(c[1] as f32 / 255.0),
+                                           srgb_decode(c[2] as f32 / 255.0));
+        ImageTexture {
+            width: image.width as usize,
+            height: image.height as usize,
+            pixels: image.data.chunks_exact(3).map(decode).collect(),
+            wrap,
+        }
+    }
+
+    // Loads a PNG or a P3/P6 PPM, told apart by their first bytes.
+    pub fn read_file(path: &Path, wrap: Wrap) -> io::Result<ImageTexture> {
+        let bytes = fs::read(path)?;
+        let image = if bytes.starts_with(b"P3") || bytes.starts_with(b"P6") {
+            PPM::decode(&bytes)?.image
+        } else {
+            PNG::decode(&bytes)?.image
+        };
+        Ok(ImageTexture::new(&image, wrap))
+    }
+
+    fn pixel(&self, i: i64, j: i64) -> Point {
+        self.pixels[self.wrap.index(j, self.height) * self.width + self.wrap.index(i, self.width)]
+    }
+}
+
+impl Texture for ImageTexture {
+    fn value(&self, u: f32, v: f32, _p: Point) -> Point {
+        if self.width == 0 || self.height == 0 {
+            return Point::new(0.0, 1.0, 1.0);
+        }
+        // Continuous pixel coordinates, with pixel centers on whole numbers.
+        let x = u * self.width as f32 - 0.5;
+        let y = (1.0 - v) * self.height as f32 - 0.5;
+        let (x0, y0) = (x.floor(), y.floor());
+        let (fx, fy) = (x - x0, y - y0);
+        let (i, j) = (x0 as i64, y0 as i64);
+        let top = (1.0 - fx) * self.pixel(i, j) + fx * self.pixel(i + 1, j);
+        let bottom = (1.0 - fx) * self.pixel(i, j + 1) + fx * self.pixel(i + 1, j + 1);
+        (1.0 - fy) * top + fy * bottom
+    }
+}
+
+#[cfg(test)]
+#[test]
+fn checker_alternates_in_every_direction() {
+    let white = Point::new(1.0, 1.0, 1.0);
+    let black = Point::new(0.0, 0.0, 0.0);
+    let checker = Checker::new(Arc::new(SolidColor::new(white)), Arc::new(SolidColor::new(black)), 0.5);
+    assert_eq!(checker.value(0.0, 0.0, Point::new(0.1, 0.1, 0.1)), white);
+    assert_eq!(checker.value(0.0, 0.0, Point::new(0.6, 0.1, 0.1)), black);
+    assert_eq!(checker.value(0.0, 0.0, Point::new(0.6, 0.6, 0.1)), white);
+    assert_eq!(checker.value(0.0, 0.0, Point::new(-0.1, 0.1, 0.1)), black);
+    assert_eq!(checker.value(0.0, 0.0, Point::new(-0.1, -0.1, -0.1)), black);
+}
+
+#[test]
+fn image_is_addressed_from_the_bottom_left() {
+    let mut image = Image::new(2, 2);
+    image.data.copy_from_slice(&[255, 0, 0, 0, 255, 0,     // top row: red, green
+                                 0, 0, 255, 255, 255, 255]); // bottom row: blue, white
+    let texture = ImageTexture::new(&image, Wrap::Clamp);
+    let p = Point::default();
+    assert_eq!(texture.value(0.1, 0.9, p), Point::new(1.0, 0.0, 0.0));
+    assert_eq!(texture.value(0.9, 0.9, p), Point::new(0.0, 1.0, 0.0));
+    assert_eq!(texture.value(0.1, 0.1, p), Point::new(0.0, 0.0, 1.0));
+    assert_eq!(texture.value(1.0, 0.0, p), Point::new(1.0, 1.0, 1.0));
+    assert_eq!(texture.value(-3.0, 7.0, p), Point::new(1.0, 0.0, 0.0));
+}
+
+#[test]
+fn filters_between_pixel_centers() {
+    let mut image = Image::new(2, 1);
+    image.data.copy_from_slice(&[0, 0, 0, 255, 255, 255]);
+    let p = Point::default();
+    let clamp = ImageTexture::new(&image, Wrap::Clamp);
+    assert!((clamp.value(0.5, 0.5, p)[0] - 0.5).abs() < 1e-6);
+    assert!((clamp.value(0.375, 0.5, p)[0] - 0.25).abs() < 1e-6);
+    assert_eq!(clamp.value(0.0, 0.5, p)[0], 0.0);
+    // Repeating blends the last column back into the first one.
+    let repeat = ImageTexture::new(&image, Wrap::Repeat);
+    assert!((repeat.value(0.0, 0.5, p)[0] - 0.5).abs() < 1e-6);
+    assert!((repeat.value(1.25, 0.5, p)[0] - repeat.value(0.25, 0.5, p)[0]).abs() < 1e-6);
+}
+
+#[test]
+fn loads_ppm_and_png_alike() {
+    let mut image = Image::new(3, 2);
+    for (i, value) in image.data.iter_mut().enumerate() {
+        *value = (i * 40) as u8;
+    }
+    let dir = std::env::temp_dir().join(format!("texture_test_{}", std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    let (ppm, png) = (dir.join("t.ppm"), dir.join("t.png"));
+    PPM::new(image.clone()).write_file(&ppm).unwrap();
+    PNG::new(image.clone()).write_file(&png).unwrap();
+    let a = ImageTexture::read_file(&ppm, Wrap::Repeat).unwrap();
+    let b = ImageTexture::read_file(&png, Wrap::Repeat).unwrap();
+    fs::remove_dir_all(&dir).unwrap();
+    assert_eq!((a.width, a.height), (3, 2));
+    assert_eq!(a.pixels, b.pixels);
+    assert_eq!(a.pixels, ImageTexture::new(&image, Wrap::Repeat).pixels);
+}
+
+#[test]
+fn noise_patterns_blend_their_colors() {
+    let black = Point::new(0.0, 0.0, 0.0);
+    let white = Point::new(1.0, 1.0, 1.0);
+    for pattern in [Pattern::Noise, Pattern::Marble, Pattern::Wood] {
+        let texture = NoiseTexture::new(pattern, 1, 4.0, Arc::new(SolidColor::new(black)), Arc::new(SolidColor::new(white)));
+        let mut seen = (f32::INFINITY, f32::NEG_INFINITY);
+        for i in 0..500 {
+            let p = Point::new(i as f32 * 0.031, (i % 7) as f32 * 0.2, i as f32 * 0.017);
+            let color = texture.value(0.0, 0.0, p);
+            assert!(color[0] == color[2] && (0.0..=1.0).contains(&color[0]), "{:?}", pattern);
+            assert_eq!(color[0], texture.amount(p));
+            seen = (seen.0.min(color[0]), seen.1.max(color[0]));
+        }
+        assert!(seen.1 - seen.0 > 0.3, "{:?} barely varies", pattern);
+    }
+    assert_eq!(Pattern::from_name("Marble"), Some(Pattern::Marble));
+    assert_eq!(Pattern::from_name("granite"), None);
+}
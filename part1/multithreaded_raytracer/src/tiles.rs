@@ -0,0 +1,91 @@
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::mpsc;
+use std::thread;
+
+pub const TILE_SIZE: u32 = 32;
+
+// Rectangle of pixels handed to one worker at a time.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Tile {
+    pub x: u32,
+    pub y: u32,
+    pub width: u32,
+    pub height: u32,
+}
+
+// Splits the image into row-major tiles. Tiles on the right and bottom edges
+// are cut down to fit.
+pub fn split_into_tiles(width: u32, height: u32, tile_size: u32) -> Vec<Tile> {
+    let mut tiles = Vec::new();
+    for y in (0..height).step_by(tile_size as usize) {
+        for x in (0..width).step_by(tile_size as usize) {
+            tiles.push(Tile {
+                x,
+                y,
+                width: tile_size.min(width - x),
+                height: tile_size.min(height - y),
+            });
+        }
+    }
+    tiles
+}
+
+// Runs `render_tile` over every tile on a fixed number of worker threads. Workers
+// pull the next tile off a shared counter; results come back to the calling
+// thread through `on_done` in the order they finish.
+pub fn render_tiles<T, F, G>(tiles: &[Tile], threads: usize, render_tile: F, mut on_done: G)
+    where T: Send,
+          F: Fn(Tile) -> T + Sync,
+          G: FnMut(T)
+{
+    let next_tile = AtomicUsize::new(0);
+    let (sender, receiver) = mpsc::channel();
+    thread::scope(|s| {
+        for _ in 0..threads.clamp(1, tiles.len().max(1)) {
+            let sender = sender.clone();
+            let next_tile = &next_tile;
+            let render_tile = &render_tile;
+            s.spawn(move || {
+                while let Some(&tile) = tiles.get(next_tile.fetch_add(1, Ordering::Relaxed)) {
+                    if sender.send(render_tile(tile)).is_err() {
+                        break;
+                    }
+                }
+            });
+        }
+        drop(sender);
+        for result in receiver {
+            on_done(result);
+        }
+    });
+}
+
+#[cfg(test)]
+#[test]
+fn tiles_cover_the_image_once() {
+    let tiles = split_into_tiles(70, 33, 32);
+    assert_eq!(tiles.len(), 6);
+    assert_eq!(tiles[2], Tile { x: 64, y: 0, width: 6, height: 32 });
+    assert_eq!(tiles[5], Tile { x: 64, y: 32, width: 6, height: 1 });
+
+    let mut covered = vec![0; 70 * 33];
+    for tile in tiles {
+        for y in tile.y..tile.y + tile.height {
+            for x in tile.x..tile.x + tile.width {
+                covered[(y * 70 + x) as usize] += 1;
+            }
+        }
+    }
+    assert!(covered.iter().all(|&c| c == 1));
+}
+
+#[test]
+fn every_tile_is_rendered_once() {
+    let tiles = split_into_tiles(100, 100, 8);
+    let mut seen = Vec::new();
+    render_tiles(&tiles, 4, |tile| (tile.x, tile.y), |xy| seen.push(xy));
+    seen.sort();
+    let mut expected: Vec<_> = tiles.iter().map(|t| (t.x, t.y)).collect();
+    expected.sort();
+    assert_eq!(seen, expected);
+}
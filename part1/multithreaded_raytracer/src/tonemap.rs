@@ -0,0 +1,143 @@
+use crate::point::Point;
+use crate::utility::{clamp, luminance};
+
+// Curve that squeezes linear radiance into the displayable 0..1 range.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Operator {
+    // Cuts everything above 1.0 off.
+    Clamp,
+    // L / (1 + L) on luminance, keeping the hue.
+    Reinhard,
+    // Narkowicz's fit of the ACES filmic curve, per channel.
+    Aces,
+}
+
+impl Operator {
+    pub fn from_name(name: &str) -> Option<Operator> {
+        match name.to_ascii_lowercase().as_str() {
+            "clamp" => Some(Operator::Clamp),
+            "reinhard" => Some(Operator::Reinhard),
+            "aces" => Some(Operator::Aces),
+            _ => None,
+        }
+    }
+}
+
+// Turns the linear framebuffer into display values: exposure, then the
+// operator, then the sRGB transfer curve.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Tonemap {
+    pub operator: Operator,
+    // In stops, so +1 doubles the radiance before tonemapping.
+    pub exposure: f32,
+}
+
+impl Tonemap {
+    pub fn new(operator: Operator, exposure: f32) -> Tonemap {
+        Tonemap {
+            operator,
+            exposure,
+        }
+    }
+
+    // Linear display color, each channel in 0..1.
+    pub fn apply(&self, color: Point) -> Point {
+        let color = 2f32.powf(self.exposure) * color;
+        let mapped = match self.operator {
+            Operator::Clamp => color,
+            Operator::Reinhard => {
+                let luminance = luminance(color);
+                if luminance > 0.0 { color / (1.0 + luminance) } else { color }
+            }
+            Operator::Aces => Point::new(aces(color[0]), aces(color[1]), aces(color[2])),
+        };
+        Point::new(saturate(mapped[0]), saturate(mapped[1]), saturate(mapped[2]))
+    }
+
+    pub fn to_rgb8(&self, color: Point) -> [u8; 3] {
+        let mapped = self.apply(color);
+        let quantize = |c: f32| (255.0 * srgb_encode(c)).round() as u8;
+        [quantize(mapped[0]), quantize(mapped[1]), quantize(mapped[2])]
+    }
+}
+
+impl Default for Tonemap {
+    fn default() -> Self {
+        Tonemap::new(Operator::Clamp, 0.0)
+    }
+}
+
+// NaN from a degenerate sample would otherwise turn into a random byte.
+fn saturate(c: f32) -> f32 {
+    if c.is_nan() { 0.0 } else { clamp(c, 0.0, 1.0) }
+}
+
+fn aces(x: f32) -> f32 {
+    let x = x.max(0.0);
+    (x * (2.51 * x + 0.03)) / (x * (2.43 * x + 0.59) + 0.14)
+}
+
+// The piecewise sRGB transfer function, linear 0..1 in and encoded 0..1 out.
+pub fn srgb_encode(linear: f32) -> f32 {
+    if linear <= 0.0031308 {
+        12.92 * linear
+    } else {
+        1.055 * linear.powf(1.0 / 2.4) - 0.055
+    }
+}
+
+// Inverse of `srgb_encode`, for reading 8-bit images back into linear color.
+pub fn srgb_decode(encoded: f32) -> f32 {
+    if encoded <= 0.04045 {
+        encoded / 12.92
+    } else {
+        ((encoded + 0.055) / 1.055).powf(2.4)
+    }
+}
+
+#[cfg(test)]
+#[test]
+fn srgb_curve_end_points() {
+    assert_eq!(srgb_encode(0.0), 0.0);
+    assert!((srgb_encode(1.0) - 1.0).abs() < 1e-6);
+    assert!((srgb_encode(0.18) - 0.4614).abs() < 1e-3);
+    // Continuous where the linear toe meets the power segment.
+    assert!((srgb_encode(0.0031308) - (1.055 * 0.0031308f32.powf(1.0 / 2.4) - 0.055)).abs() < 1e-6);
+    for x in [0.0, 0.002, 0.04, 0.18, 0.5, 1.0] {
+        assert!((srgb_decode(srgb_encode(x)) - x).abs() < 1e-5);
+    }
+}
+
+#[test]
+fn operators_keep_highlights_apart() {
+    let bright = Point::new(4.0, 4.0, 4.0);
+    let brighter = Point::new(16.0, 16.0, 16.0);
+    let clamp = Tonemap::default();
+    assert_eq!(clamp.to_rgb8(bright), clamp.to_rgb8(brighter));
+    for operator in [Operator::Reinhard, Operator::Aces] {
+        let tonemap = Tonemap::new(operator, 0.0);
+        let (a, b) = (tonemap.apply(bright), tonemap.apply(brighter));
+        assert!(a[0] < b[0] && b[0] <= 1.0, "{:?}", operator);
+    }
+}
+
+#[test]
+fn reinhard_keeps_the_hue() {
+    let mapped = Tonemap::new(Operator::Reinhard, 0.0).apply(Point::new(2.0, 1.0, 0.5));
+    assert!((mapped[0] / mapped[1] - 2.0).abs() < 1e-5);
+    assert!((mapped[1] / mapped[2] - 2.0).abs() < 1e-5);
+}
+
+#[test]
+fn exposure_is_in_stops() {
+    let tonemap = Tonemap::new(Operator::Clamp, -2.0);
+    assert_eq!(tonemap.apply(Point::new(2.0, 0.4, 0.0)), Point::new(0.5, 0.1, 0.0));
+    assert_eq!(tonemap.to_rgb8(Point::new(f32::NAN, 4.0, -1.0)), [0, 255, 0]);
+}
+
+#[test]
+fn names() {
+    assert_eq!(Operator::from_name("ACES"), Some(Operator::Aces));
+    assert_eq!(Operator::from_name("reinhard"), Some(Operator::Reinhard));
+    assert_eq!(Operator::from_name("filmic"), None);
+}
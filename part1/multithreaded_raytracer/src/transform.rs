@@ -0,0 +1,128 @@
+use std::sync::Arc;
+
+use rand::RngCore;
+
+use crate::{Point, Ray};
+use crate::aabb::AABB;
+use crate::hittable::{HitRecord, Hittable};
+use crate::matrix::Matrix4;
+use crate::utility::unit_vector;
+
+// Places a shared object in the world with an affine transform. Rays are taken
+// into object space instead of moving the object, so any number of instances
+// can point at one mesh.
+pub struct Transform {
+    pub object: Arc<dyn Hittable>,
+    pub to_world: Matrix4,
+    to_object: Matrix4,
+    // Normals go back to world space by the inverse transpose.
+    normal_to_world: Matrix4,
+    bounding_box: Option<AABB>,
+}
+
+impl Transform {
+    /// None if `to_world` can't be inverted, e.g. when it scales an axis to zero.
+    pub fn new(object: Arc<dyn Hittable>, to_world: Matrix4) -> Option<Transform> {
+        let to_object = to_world.inverse()?;
+        let bounding_box = object.get_bounding_box().map(|b| {
+            let corners: Vec<Point> = (0..8)
+                .map(|i| Point::new(if i & 1 == 0 { b.min.x } else { b.max.x },
+                                    if i & 2 == 0 { b.min.y } else { b.max.y },
+                                    if i & 4 == 0 { b.min.z } else { b.max.z }))
+                .map(|corner| to_world.transform_point(corner))
+                .collect();
+            corners[1..].iter().fold(AABB::new(corners[0], corners[0]), |b, &c| AABB::surrounding_box(b, AABB::new(c, c)))
+        });
+        Some(Transform {
+            object,
+            to_world,
+            to_object,
+            normal_to_world: to_object.transpose(),
+            bounding_box,
+        })
+    }
+}
+
+impl Hittable for Transform {
+    fn hit(&self, r: Ray, t_min: f32, t_max: f32, rng: &mut dyn RngCore) -> Option<HitRecord<'_>> {
+        // The direction isn't renormalized, so t means the same in both spaces.
+        let object_ray = Ray::with_time(self.to_object.transform_point(r.origin), self.to_object.transform_vector(r.direction), r.time);
+        let mut rec = self.object.hit(object_ray, t_min, t_max, rng)?;
+        rec.p = self.to_world.transform_point(rec.p);
+        rec.normal = unit_vector(self.normal_to_world.transform_vector(rec.normal));
+        Some(rec)
+    }
+
+    fn get_bounding_box(&self) -> Option<AABB> {
+        self.bounding_box
+    }
+}
+
+#[cfg(test)]
+fn unit_box() -> Arc<dyn Hittable> {
+    use crate::cuboid::Cuboid;
+    use crate::material::Lambertian;
+
+    Arc::new(Cuboid::new(Point::new(-1.0, -1.0, -1.0), Point::new(1.0, 1.0, 1.0),
+                         Arc::new(Lambertian::new(Point::new(0.5, 0.5, 0.5)))))
+}
+
+#[test]
+fn moves_and_scales_hits() {
+    let mut rng = crate::random::Pcg32::new(7, 0);
+    let m = Matrix4::translation(Point::new(0.0, 0.0, -10.0)) * Matrix4::scaling(Point::new(1.0, 1.0, 3.0));
+    let instance = Transform::new(unit_box(), m).unwrap();
+
+    let rec = instance.hit(Ray::new(Point::default(), Point::new(0.0, 0.0, -1.0)), 0.001, f32::INFINITY, &mut rng).unwrap();
+    assert!((rec.t - 7.0).abs() < 1e-5);
+    assert!((rec.p - Point::new(0.0, 0.0, -7.0)).length() < 1e-5);
+    assert!((rec.normal - Point::new(0.0, 0.0, 1.0)).length() < 1e-5);
+    assert!(rec.front_face);
+
+    let bounding_box = instance.get_bounding_box().unwrap();
+    assert!((bounding_box.min - Point::new(-1.0, -1.0, -13.0)).length() < 1e-5);
+    assert!((bounding_box.max - Point::new(1.0, 1.0, -7.0)).length() < 1e-5);
+}
+
+#[test]
+fn rotated_normals_stay_perpendicular() {
+    let mut rng = crate::random::Pcg32::new(7, 0);
+    // Turn 45 degrees about z then squash, which shears the box so its face
+    // normals only come out right through the inverse transpose.
+    let m = Matrix4::scaling(Point::new(2.0, 0.5, 1.0)) * Matrix4::rotation(2, 45.0);
+    let instance = Transform::new(unit_box(), m).unwrap();
+
+    // Straight down onto the top face (y = 1) in object space.
+    let origin = m.transform_point(Point::new(0.0, 5.0, 0.0));
+    let r = Ray::new(origin, m.transform_point(Point::new(0.0, 1.0, 0.0)) - origin);
+    let rec = instance.hit(r, 0.001, f32::INFINITY, &mut rng).unwrap();
+    let along_face = m.transform_vector(Point::new(1.0, 0.0, 0.0));
+    assert!(crate::utility::dot(rec.normal, along_face).abs() < 1e-5);
+    assert!((rec.normal.length() - 1.0).abs() < 1e-5);
+    assert!(crate::utility::dot(rec.normal, r.direction) < 0.0);
+    assert!((rec.t - 1.0).abs() < 1e-5);
+
+    let bounding_box = instance.get_bounding_box().unwrap();
+    assert!(bounding_box.hit_box(r, 0.001, f32::INFINITY));
+}
+
+#[test]
+fn instances_share_one_object() {
+    use crate::hittable_list::{CheckHits, HittableList};
+
+    let mut rng = crate::random::Pcg32::new(7, 0);
+    let shared = unit_box();
+    let mut world = HittableList::new();
+    for i in 0..100 {
+        world.add(Transform::new(shared.clone(), Matrix4::translation(Point::new(3.0 * i as f32, 0.0, 0.0))).unwrap());
+    }
+    assert_eq!(Arc::strong_count(&shared), 101);
+    let rec = world.get_hits(Ray::new(Point::new(150.0, 10.0, 0.0), Point::new(0.0, -1.0, 0.0)), 0.001, f32::INFINITY, &mut rng).unwrap();
+    assert!((rec.p.y - 1.0).abs() < 1e-5);
+}
+
+#[test]
+fn refuses_transforms_it_cannot_undo() {
+    assert!(Transform::new(unit_box(), Matrix4::scaling(Point::new(1.0, 0.0, 1.0))).is_none());
+    assert!(Transform::new(unit_box(), Matrix4::scaling(Point::new(1e-13, 1.0, 1.0))).is_none());
+}
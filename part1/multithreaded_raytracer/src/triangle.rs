@@ -0,0 +1,113 @@
+use std::sync::Arc;
+
+use rand::RngCore;
+
+use crate::{Point, Ray};
+use crate::aabb::AABB;
+use crate::hittable::{HitRecord, Hittable};
+use crate::material::Material;
+use crate::utility::{cross, dot, unit_vector};
+
+const EPSILON: f32 = 1e-8;
+
+// Flat triangles are as thin as rectangles, so their boxes get padded too.
+const PAD: f32 = 0.0001;
+
+/// Möller–Trumbore ray/triangle test. Returns the distance along the ray and
+/// the barycentric weights (b1, b2) of `v1` and `v2`; `v0` gets 1 - b1 - b2.
+pub fn intersect(r: Ray, v0: Point, v1: Point, v2: Point, t_min: f32, t_max: f32) -> Option<(f32, f32, f32)> {
+    let edge1 = v1 - v0;
+    let edge2 = v2 - v0;
+    let p = cross(r.direction, edge2);
+    let determinant = dot(edge1, p);
+    if determinant.abs() < EPSILON {
+        return None;
+    }
+    let inverse = 1.0 / determinant;
+    let s = r.origin - v0;
+    let b1 = dot(s, p) * inverse;
+    if !(0.0..=1.0).contains(&b1) {
+        return None;
+    }
+    let q = cross(s, edge1);
+    let b2 = dot(r.direction, q) * inverse;
+    if b2 < 0.0 || b1 + b2 > 1.0 {
+        return None;
+    }
+    let t = dot(edge2, q) * inverse;
+    if t < t_min || t > t_max {
+        return None;
+    }
+    Some((t, b1, b2))
+}
+
+pub fn triangle_box(v0: Point, v1: Point, v2: Point) -> AABB {
+    let pad = Point::new(PAD, PAD, PAD);
+    let min = Point::new(v0.x.min(v1.x).min(v2.x), v0.y.min(v1.y).min(v2.y), v0.z.min(v1.z).min(v2.z));
+    let max = Point::new(v0.x.max(v1.x).max(v2.x), v0.y.max(v1.y).max(v2.y), v0.z.max(v1.z).max(v2.z));
+    AABB::new(min - pad, max + pad)
+}
+
+// Single triangle with a flat normal. Meshes store their triangles more
+// compactly, see `Mesh`.
+#[derive(Clone)]
+pub struct Triangle {
+    pub vertices: [Point; 3],
+    pub material: Arc<dyn Material>,
+}
+
+impl Triangle {
+    pub fn new(v0: Point, v1: Point, v2: Point, material: Arc<dyn Material>) -> Triangle {
+        Triangle {
+            vertices: [v0, v1, v2],
+            material,
+        }
+    }
+}
+
+impl Hittable for Triangle {
+    fn hit(&self, r: Ray, t_min: f32, t_max: f32, _rng: &mut dyn RngCore) -> Option<HitRecord<'_>> {
+        let [v0, v1, v2] = self.vertices;
+        let (t, b1, b2) = intersect(r, v0, v1, v2, t_min, t_max)?;
+        let outward_normal = unit_vector(cross(v1 - v0, v2 - v0));
+        let mut rec = HitRecord {
+            p: r.at(t),
+            normal: Point::default(),
+            t,
+            front_face: true,
+            u: b1,
+            v: b2,
+            barycentric: (b1, b2),
+            material: self.material.as_ref(),
+        };
+        rec.set_face_normal(r, outward_normal);
+        Some(rec)
+    }
+
+    fn get_bounding_box(&self) -> Option<AABB> {
+        let [v0, v1, v2] = self.vertices;
+        Some(triangle_box(v0, v1, v2))
+    }
+}
+
+#[cfg(test)]
+#[test]
+fn reports_barycentrics() {
+    let mut rng = crate::random::Pcg32::new(7, 0);
+    let grey = Arc::new(crate::material::Lambertian::new(Point::new(0.5, 0.5, 0.5)));
+    let triangle = Triangle::new(Point::new(0.0, 0.0, -2.0), Point::new(1.0, 0.0, -2.0), Point::new(0.0, 1.0, -2.0), grey);
+
+    let rec = triangle.hit(Ray::new(Point::new(0.25, 0.5, 0.0), Point::new(0.0, 0.0, -1.0)), 0.001, f32::INFINITY, &mut rng).unwrap();
+    assert!((rec.t - 2.0).abs() < 1e-6);
+    assert!((rec.barycentric.0 - 0.25).abs() < 1e-6 && (rec.barycentric.1 - 0.5).abs() < 1e-6);
+    assert_eq!(rec.normal, Point::new(0.0, 0.0, 1.0));
+    assert!(rec.front_face);
+
+    let from_behind = triangle.hit(Ray::new(Point::new(0.25, 0.25, -4.0), Point::new(0.0, 0.0, 1.0)), 0.001, f32::INFINITY, &mut rng).unwrap();
+    assert!(!from_behind.front_face);
+    assert_eq!(from_behind.normal, Point::new(0.0, 0.0, -1.0));
+
+    assert!(triangle.hit(Ray::new(Point::new(0.6, 0.6, 0.0), Point::new(0.0, 0.0, -1.0)), 0.001, f32::INFINITY, &mut rng).is_none());
+    assert!(triangle.hit(Ray::new(Point::new(0.2, 0.2, 0.0), Point::new(1.0, 0.0, 0.0)), 0.001, f32::INFINITY, &mut rng).is_none());
+    assert!(triangle.hit(Ray::new(Point::new(0.2, 0.2, 0.0), Point::new(0.0, 0.0, -1.0)), 0.001, 1.0, &mut rng).is_none());
+}
@@ -1,5 +1,4 @@
 use rand::Rng;
-use rand::rngs::ThreadRng;
 
 use crate::Point;
 
@@ -9,7 +8,7 @@ pub fn clamp(input: f32, min: f32, max: f32) -> f32 {
     } else if input > max {
         return max;
     }
-    return input;
+    input
 }
 
 pub fn refract(uv: Point, n: Point, etai_over_etat: f32) -> Point {
@@ -22,16 +21,16 @@ pub fn refract(uv: Point, n: Point, etai_over_etat: f32) -> Point {
     r_out_perp + r_out_parallel
 }
 
-pub fn random_point_range(rng: &mut ThreadRng, min: f32, max: f32) -> Point {
+pub fn random_point_range<R: Rng + ?Sized>(rng: &mut R, min: f32, max: f32) -> Point {
     Point::new(rng.gen_range(min..max), rng.gen_range(min..max), rng.gen_range(min..max))
 }
 
 pub fn reflect(v: Point, n: Point) -> Point {
     let s = 2.0 * dot(v, n);
-    v.clone() - s * n.clone()
+    v - s * n
 }
 
-pub fn random_in_unit_sphere(rng: &mut ThreadRng) -> Point {
+pub fn random_in_unit_sphere<R: Rng + ?Sized>(rng: &mut R) -> Point {
     loop {
         let p = random_point_range(rng, -1.0, 1.0);
         if p.length_squared() >= 1.0 { continue; }
@@ -39,7 +38,7 @@ pub fn random_in_unit_sphere(rng: &mut ThreadRng) -> Point {
     }
 }
 
-pub fn random_unit_vector(rng: &mut ThreadRng) -> Point {
+pub fn random_unit_vector<R: Rng + ?Sized>(rng: &mut R) -> Point {
     unit_vector(random_in_unit_sphere(rng))
 }
 
@@ -64,7 +63,12 @@ pub fn dot(u: Point, v: Point) -> f32 {
         u[2] * v[2]
 }
 
-pub fn random_in_unit_disk(rng: &mut ThreadRng) -> Point {
+// Rec. 709 luminance of a linear color.
+pub fn luminance(color: Point) -> f32 {
+    0.2126 * color[0] + 0.7152 * color[1] + 0.0722 * color[2]
+}
+
+pub fn random_in_unit_disk<R: Rng + ?Sized>(rng: &mut R) -> Point {
     loop {
         let x = rng.gen_range(-1.0..1.0);
         let y = rng.gen_range(-1.0..1.0);
@@ -74,7 +78,50 @@ pub fn random_in_unit_disk(rng: &mut ThreadRng) -> Point {
     }
 }
 
+// The maps below turn uniform samples from a Sampler into points, keeping
+// their stratification where the rejection loops above would throw it away.
+
+// Uniform on the unit sphere's surface.
+pub fn sample_unit_vector(u: [f32; 2]) -> Point {
+    let z = 1.0 - 2.0 * u[0];
+    let r = (1.0 - z * z).max(0.0).sqrt();
+    let (sin, cos) = (2.0 * std::f32::consts::PI * u[1]).sin_cos();
+    Point::new(r * cos, r * sin, z)
+}
+
+// Uniform inside the unit sphere, the radius coming from a third sample.
+pub fn sample_in_unit_sphere(u: [f32; 2], radius: f32) -> Point {
+    radius.cbrt() * sample_unit_vector(u)
+}
+
+// Shirley and Chiu's concentric map from the square onto the unit disk in the
+// xy plane, which keeps neighbouring samples together.
+pub fn sample_in_unit_disk(u: [f32; 2]) -> Point {
+    let (a, b) = (2.0 * u[0] - 1.0, 2.0 * u[1] - 1.0);
+    if a == 0.0 && b == 0.0 {
+        return Point::new(0.0, 0.0, 0.0);
+    }
+    let quarter = std::f32::consts::FRAC_PI_4;
+    let (r, theta) = if a.abs() > b.abs() { (a, quarter * (b / a)) } else { (b, 2.0 * quarter - quarter * (a / b)) };
+    Point::new(r * theta.cos(), r * theta.sin(), 0.0)
+}
+
 #[cfg(test)]
+#[test]
+fn sample_maps_stay_in_shape() {
+    for i in 0..=10 {
+        for j in 0..=10 {
+            let u = [i as f32 / 10.0, j as f32 / 10.0];
+            assert!((sample_unit_vector(u).length() - 1.0).abs() < 1e-5);
+            assert!(sample_in_unit_sphere(u, 0.5).length() < 0.8);
+            let p = sample_in_unit_disk(u);
+            assert!(p.length() <= 1.0 + 1e-5 && p.z == 0.0);
+        }
+    }
+    assert!((sample_in_unit_disk([1.0, 0.5]) - Point::new(1.0, 0.0, 0.0)).length() < 1e-6);
+    assert!((sample_unit_vector([0.0, 0.3]) - Point::new(0.0, 0.0, 1.0)).length() < 1e-6);
+}
+
 #[test]
 fn dot_prod() {
     let p = Point::new(10.0, 20.0, 30.0);
@@ -0,0 +1,449 @@
+// Just enough zlib (RFC 1950) and deflate (RFC 1951) for PNG files.
+//
+// `compress` writes a single fixed-Huffman block with greedy LZ77 matching.
+// `decompress` handles stored, fixed and dynamic blocks.
+
+use std::io;
+
+const WINDOW_SIZE: usize = 32768;
+const MIN_MATCH: usize = 3;
+const MAX_MATCH: usize = 258;
+const MAX_CHAIN: usize = 64;
+const HASH_BITS: u32 = 15;
+
+const LENGTH_BASE: [u16; 29] = [
+    3, 4, 5, 6, 7, 8, 9, 10, 11, 13, 15, 17, 19, 23, 27, 31,
+    35, 43, 51, 59, 67, 83, 99, 115, 131, 163, 195, 227, 258,
+];
+const LENGTH_EXTRA: [u8; 29] = [
+    0, 0, 0, 0, 0, 0, 0, 0, 1, 1, 1, 1, 2, 2, 2, 2,
+    3, 3, 3, 3, 4, 4, 4, 4, 5, 5, 5, 5, 0,
+];
+const DIST_BASE: [u16; 30] = [
+    1, 2, 3, 4, 5, 7, 9, 13, 17, 25, 33, 49, 65, 97, 129, 193,
+    257, 385, 513, 769, 1025, 1537, 2049, 3073, 4097, 6145, 8193, 12289, 16385, 24577,
+];
+const DIST_EXTRA: [u8; 30] = [
+    0, 0, 0, 0, 1, 1, 2, 2, 3, 3, 4, 4, 5, 5, 6, 6,
+    7, 7, 8, 8, 9, 9, 10, 10, 11, 11, 12, 12, 13, 13,
+];
+// Order the code length code lengths are sent in for dynamic blocks.
+const CODE_LENGTH_ORDER: [usize; 19] = [16, 17, 18, 0, 8, 7, 9, 6, 10, 5, 11, 4, 12, 3, 13, 2, 14, 1, 15];
+
+pub fn adler32(data: &[u8]) -> u32 {
+    let mut a: u32 = 1;
+    let mut b: u32 = 0;
+    // 5552 is the most bytes that can be summed before b overflows.
+    for chunk in data.chunks(5552) {
+        for &byte in chunk {
+            a += byte as u32;
+            b += a;
+        }
+        a %= 65521;
+        b %= 65521;
+    }
+    (b << 16) | a
+}
+
+struct BitWriter {
+    out: Vec<u8>,
+    bit_buffer: u32,
+    bit_count: u32,
+}
+
+impl BitWriter {
+    fn new(out: Vec<u8>) -> BitWriter {
+        BitWriter {
+            out,
+            bit_buffer: 0,
+            bit_count: 0,
+        }
+    }
+
+    // Writes the low `count` bits of `value`, least significant bit first.
+    fn write_bits(&mut self, value: u32, count: u32) {
+        self.bit_buffer |= value << self.bit_count;
+        self.bit_count += count;
+        while self.bit_count >= 8 {
+            self.out.push(self.bit_buffer as u8);
+            self.bit_buffer >>= 8;
+            self.bit_count -= 8;
+        }
+    }
+
+    // Huffman codes are packed starting from their most significant bit.
+    fn write_code(&mut self, code: u32, length: u32) {
+        let reversed = code.reverse_bits() >> (32 - length);
+        self.write_bits(reversed, length);
+    }
+
+    fn finish(mut self) -> Vec<u8> {
+        if self.bit_count > 0 {
+            self.out.push(self.bit_buffer as u8);
+        }
+        self.out
+    }
+}
+
+fn write_literal(writer: &mut BitWriter, symbol: u32) {
+    match symbol {
+        0..=143 => writer.write_code(0x30 + symbol, 8),
+        144..=255 => writer.write_code(0x190 + symbol - 144, 9),
+        256..=279 => writer.write_code(symbol - 256, 7),
+        _ => writer.write_code(0xc0 + symbol - 280, 8),
+    }
+}
+
+fn write_match(writer: &mut BitWriter, length: usize, distance: usize) {
+    let length_code = LENGTH_BASE.iter().rposition(|&base| base as usize <= length).unwrap();
+    write_literal(writer, 257 + length_code as u32);
+    writer.write_bits((length - LENGTH_BASE[length_code] as usize) as u32, LENGTH_EXTRA[length_code] as u32);
+
+    let dist_code = DIST_BASE.iter().rposition(|&base| base as usize <= distance).unwrap();
+    writer.write_code(dist_code as u32, 5);
+    writer.write_bits((distance - DIST_BASE[dist_code] as usize) as u32, DIST_EXTRA[dist_code] as u32);
+}
+
+fn hash(data: &[u8], i: usize) -> usize {
+    let v = (data[i] as u32) << 16 | (data[i + 1] as u32) << 8 | data[i + 2] as u32;
+    (v.wrapping_mul(2654435761) >> (32 - HASH_BITS)) as usize
+}
+
+pub fn compress(data: &[u8]) -> Vec<u8> {
+    // CMF: deflate with a 32K window. FLG: default compression, check bits.
+    let mut writer = BitWriter::new(vec![0x78, 0x9c]);
+    writer.write_bits(1, 1); // last block
+    writer.write_bits(1, 2); // fixed Huffman codes
+
+    let mut head = vec![usize::MAX; 1 << HASH_BITS];
+    let mut prev = vec![usize::MAX; WINDOW_SIZE];
+    let insert = |head: &mut Vec<usize>, prev: &mut Vec<usize>, i: usize| {
+        if i + MIN_MATCH <= data.len() {
+            let h = hash(data, i);
+            prev[i % WINDOW_SIZE] = head[h];
+            head[h] = i;
+        }
+    };
+
+    let mut i = 0;
+    while i < data.len() {
+        let mut best_length = 0;
+        let mut best_distance = 0;
+        if i + MIN_MATCH <= data.len() {
+            let max_length = MAX_MATCH.min(data.len() - i);
+            let mut candidate = head[hash(data, i)];
+            let mut chain = 0;
+            while candidate != usize::MAX && i - candidate <= WINDOW_SIZE && chain < MAX_CHAIN {
+                let length = data[candidate..].iter()
+                    .zip(&data[i..i + max_length])
+                    .take_while(|(a, b)| a == b)
+                    .count();
+                if length > best_length {
+                    best_length = length;
+                    best_distance = i - candidate;
+                    if length == max_length {
+                        break;
+                    }
+                }
+                let next = prev[candidate % WINDOW_SIZE];
+                if next == usize::MAX || next >= candidate {
+                    break;
+                }
+                candidate = next;
+                chain += 1;
+            }
+        }
+
+        if best_length >= MIN_MATCH {
+            write_match(&mut writer, best_length, best_distance);
+            for j in i..i + best_length {
+                insert(&mut head, &mut prev, j);
+            }
+            i += best_length;
+        } else {
+            write_literal(&mut writer, data[i] as u32);
+            insert(&mut head, &mut prev, i);
+            i += 1;
+        }
+    }
+    write_literal(&mut writer, 256);
+
+    let mut out = writer.finish();
+    out.extend_from_slice(&adler32(data).to_be_bytes());
+    out
+}
+
+fn invalid(message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, format!("corrupt deflate stream: {}", message))
+}
+
+fn too_long() -> io::Error {
+    invalid("more data than expected")
+}
+
+struct BitReader<'a> {
+    data: &'a [u8],
+    position: usize,
+    bit_buffer: u32,
+    bit_count: u32,
+}
+
+impl<'a> BitReader<'a> {
+    fn read_bits(&mut self, count: u32) -> io::Result<u32> {
+        while self.bit_count < count {
+            let byte = *self.data.get(self.position).ok_or_else(|| invalid("unexpected end of data"))?;
+            self.position += 1;
+            self.bit_buffer |= (byte as u32) << self.bit_count;
+            self.bit_count += 8;
+        }
+        let value = self.bit_buffer & ((1u64 << count) - 1) as u32;
+        self.bit_buffer >>= count;
+        self.bit_count -= count;
+        Ok(value)
+    }
+
+    fn align_to_byte(&mut self) {
+        self.bit_buffer = 0;
+        self.bit_count = 0;
+    }
+}
+
+// Canonical Huffman table: how many codes of each length, and the symbols in
+// code order.
+struct Huffman {
+    counts: [u16; 16],
+    symbols: Vec<u16>,
+}
+
+impl Huffman {
+    fn new(lengths: &[u8]) -> io::Result<Huffman> {
+        let mut counts = [0u16; 16];
+        for &length in lengths {
+            counts[length as usize] += 1;
+        }
+        let mut left: i32 = 1;
+        for &count in &counts[1..] {
+            left = left * 2 - count as i32;
+            if left < 0 {
+                return Err(invalid("over-subscribed code lengths"));
+            }
+        }
+        let mut offsets = [0u16; 16];
+        for length in 1..15 {
+            offsets[length + 1] = offsets[length] + counts[length];
+        }
+        let mut symbols = vec![0u16; lengths.len()];
+        for (symbol, &length) in lengths.iter().enumerate() {
+            if length != 0 {
+                symbols[offsets[length as usize] as usize] = symbol as u16;
+                offsets[length as usize] += 1;
+            }
+        }
+        Ok(Huffman { counts, symbols })
+    }
+
+    fn decode(&self, reader: &mut BitReader) -> io::Result<u16> {
+        let mut code: i32 = 0;
+        let mut first: i32 = 0;
+        let mut index: i32 = 0;
+        for length in 1..16 {
+            code |= reader.read_bits(1)? as i32;
+            let count = self.counts[length] as i32;
+            if code - count < first {
+                return Ok(self.symbols[(index + code - first) as usize]);
+            }
+            index += count;
+            first += count;
+            first <<= 1;
+            code <<= 1;
+        }
+        Err(invalid("bad Huffman code"))
+    }
+}
+
+fn fixed_tables() -> io::Result<(Huffman, Huffman)> {
+    let mut lengths = [0u8; 288];
+    lengths[..144].fill(8);
+    lengths[144..256].fill(9);
+    lengths[256..280].fill(7);
+    lengths[280..].fill(8);
+    Ok((Huffman::new(&lengths)?, Huffman::new(&[5; 30])?))
+}
+
+fn dynamic_tables(reader: &mut BitReader) -> io::Result<(Huffman, Huffman)> {
+    let literal_count = reader.read_bits(5)? as usize + 257;
+    let distance_count = reader.read_bits(5)? as usize + 1;
+    let code_length_count = reader.read_bits(4)? as usize + 4;
+    if literal_count > 286 || distance_count > 30 {
+        return Err(invalid("too many codes"));
+    }
+
+    let mut code_length_lengths = [0u8; 19];
+    for &i in &CODE_LENGTH_ORDER[..code_length_count] {
+        code_length_lengths[i] = reader.read_bits(3)? as u8;
+    }
+    let code_length_table = Huffman::new(&code_length_lengths)?;
+
+    let mut lengths = vec![0u8; literal_count + distance_count];
+    let mut i = 0;
+    while i < lengths.len() {
+        let symbol = code_length_table.decode(reader)?;
+        let (value, repeat) = match symbol {
+            0..=15 => (symbol as u8, 1),
+            16 => {
+                let previous = *lengths[..i].last().ok_or_else(|| invalid("repeat with no previous length"))?;
+                (previous, 3 + reader.read_bits(2)? as usize)
+            }
+            17 => (0, 3 + reader.read_bits(3)? as usize),
+            _ => (0, 11 + reader.read_bits(7)? as usize),
+        };
+        if i + repeat > lengths.len() {
+            return Err(invalid("too many code lengths"));
+        }
+        lengths[i..i + repeat].fill(value);
+        i += repeat;
+    }
+    if lengths[256] == 0 {
+        return Err(invalid("no end of block code"));
+    }
+    Ok((Huffman::new(&lengths[..literal_count])?, Huffman::new(&lengths[literal_count..])?))
+}
+
+fn inflate_block(reader: &mut BitReader, out: &mut Vec<u8>, limit: usize, literals: &Huffman, distances: &Huffman) -> io::Result<()> {
+    loop {
+        let symbol = literals.decode(reader)? as usize;
+        match symbol {
+            0..=255 if out.len() < limit => out.push(symbol as u8),
+            0..=255 => return Err(too_long()),
+            256 => return Ok(()),
+            257..=285 => {
+                let code = symbol - 257;
+                let length = LENGTH_BASE[code] as usize + reader.read_bits(LENGTH_EXTRA[code] as u32)? as usize;
+                let dist_code = distances.decode(reader)? as usize;
+                if dist_code >= 30 {
+                    return Err(invalid("bad distance code"));
+                }
+                let distance = DIST_BASE[dist_code] as usize + reader.read_bits(DIST_EXTRA[dist_code] as u32)? as usize;
+                if distance > out.len() {
+                    return Err(invalid("distance too far back"));
+                }
+                if length > limit - out.len() {
+                    return Err(too_long());
+                }
+                let start = out.len() - distance;
+                for j in 0..length {
+                    out.push(out[start + j]);
+                }
+            }
+            _ => return Err(invalid("bad length code")),
+        }
+    }
+}
+
+// Inflates zlib data, failing once the output would pass `limit` bytes so a
+// small stream can't expand without end.
+pub fn decompress(data: &[u8], limit: usize) -> io::Result<Vec<u8>> {
+    if data.len() < 6 {
+        return Err(invalid("too short"));
+    }
+    let (cmf, flg) = (data[0], data[1]);
+    if cmf & 0x0f != 8 || !(cmf as u16 * 256 + flg as u16).is_multiple_of(31) {
+        return Err(invalid("bad zlib header"));
+    }
+    if flg & 0x20 != 0 {
+        return Err(invalid("preset dictionaries are not supported"));
+    }
+
+    let mut reader = BitReader { data: &data[..data.len() - 4], position: 2, bit_buffer: 0, bit_count: 0 };
+    let mut out = Vec::new();
+    loop {
+        let last = reader.read_bits(1)? == 1;
+        match reader.read_bits(2)? {
+            0 => {
+                reader.align_to_byte();
+                let p = reader.position;
+                let header = reader.data.get(p..p + 4).ok_or_else(|| invalid("unexpected end of data"))?;
+                let length = u16::from_le_bytes([header[0], header[1]]) as usize;
+                let complement = u16::from_le_bytes([header[2], header[3]]) as usize;
+                if length != !complement & 0xffff {
+                    return Err(invalid("stored block length mismatch"));
+                }
+                let block = reader.data.get(p + 4..p + 4 + length).ok_or_else(|| invalid("unexpected end of data"))?;
+                if length > limit - out.len() {
+                    return Err(too_long());
+                }
+                out.extend_from_slice(block);
+                reader.position = p + 4 + length;
+            }
+            1 => {
+                let (literals, distances) = fixed_tables()?;
+                inflate_block(&mut reader, &mut out, limit, &literals, &distances)?;
+            }
+            2 => {
+                let (literals, distances) = dynamic_tables(&mut reader)?;
+                inflate_block(&mut reader, &mut out, limit, &literals, &distances)?;
+            }
+            _ => return Err(invalid("reserved block type")),
+        }
+        if last {
+            break;
+        }
+    }
+
+    let checksum = &data[data.len() - 4..];
+    if u32::from_be_bytes([checksum[0], checksum[1], checksum[2], checksum[3]]) != adler32(&out) {
+        return Err(invalid("checksum mismatch"));
+    }
+    Ok(out)
+}
+
+#[cfg(test)]
+#[test]
+fn adler32_of_wikipedia() {
+    assert_eq!(adler32(b"Wikipedia"), 0x11e60398);
+}
+
+#[test]
+fn round_trips() {
+    let mut data = Vec::new();
+    for i in 0..20000u32 {
+        data.push((i % 251) as u8);
+        if i % 7 == 0 {
+            data.extend_from_slice(b"repeated text ");
+        }
+    }
+    let compressed = compress(&data);
+    assert!(compressed.len() < data.len() / 2);
+    assert_eq!(decompress(&compressed, data.len()).unwrap(), data);
+    assert!(decompress(&compressed, data.len() - 1).is_err());
+    assert_eq!(decompress(&compress(&[]), 0).unwrap(), Vec::<u8>::new());
+    assert_eq!(decompress(&compress(b"ab"), 2).unwrap(), b"ab");
+}
+
+#[test]
+fn reads_dynamic_blocks() {
+    // zlib.compress(text, 9) from CPython, which picks a dynamic Huffman block.
+    let compressed = [
+        0x78, 0xda, 0x95, 0xd4, 0x5b, 0x16, 0xc1, 0x60, 0x0c, 0x45, 0xe1, 0xa9, 0x64, 0x08, 0x72, 0x8e,
+        0xa2, 0xc3, 0x41, 0xeb, 0xce, 0x4f, 0xb5, 0x75, 0x19, 0x3d, 0xcb, 0x08, 0xec, 0xc7, 0xac, 0xec,
+        0xb7, 0x6f, 0x25, 0xfd, 0xae, 0x8d, 0xdb, 0xb0, 0x5f, 0x1f, 0x63, 0xd5, 0x95, 0xc7, 0x25, 0x36,
+        0xe5, 0x19, 0x87, 0xe1, 0x7c, 0xbd, 0x47, 0x19, 0xdb, 0x2e, 0xfa, 0xef, 0xfa, 0xb4, 0x7c, 0xbf,
+        0xa2, 0x29, 0xdb, 0x98, 0xfc, 0xc6, 0x7f, 0xeb, 0x44, 0xb5, 0x50, 0x6d, 0x54, 0x4f, 0x51, 0x5d,
+        0xa1, 0x7a, 0x86, 0xea, 0x39, 0xaa, 0x17, 0xa8, 0xae, 0x99, 0x0e, 0xc4, 0x64, 0x9a, 0xc9, 0x38,
+        0x93, 0x79, 0x26, 0x03, 0x4d, 0x26, 0x9a, 0x8c, 0x34, 0x99, 0x69, 0x32, 0xd4, 0x64, 0xaa, 0x62,
+        0xaa, 0x82, 0x37, 0xca, 0x54, 0xc5, 0x54, 0xc5, 0x54, 0xc5, 0x54, 0xc5, 0x54, 0xc5, 0x54, 0xc5,
+        0x54, 0xc5, 0x54, 0xcd, 0x54, 0xcd, 0x54, 0x0d, 0x5f, 0x2f, 0x53, 0x35, 0x53, 0x35, 0x53, 0x35,
+        0x53, 0x35, 0x53, 0x35, 0x53, 0x75, 0x1d, 0x1f, 0x29, 0x4f, 0x97, 0x17,
+    ];
+    let expected: String = (0..40).map(|i| format!("the quick brown fox jumps over the lazy dog {} ", i)).collect();
+    assert_eq!(decompress(&compressed, usize::MAX).unwrap(), expected.as_bytes());
+    assert!(decompress(&compressed, 100).is_err());
+}
+
+#[test]
+fn rejects_corrupt_data() {
+    let mut compressed = compress(b"hello hello hello");
+    let last = compressed.len() - 1;
+    compressed[last] ^= 1;
+    assert!(decompress(&compressed, usize::MAX).is_err());
+}